@@ -0,0 +1,3 @@
+-- Add down migration script here
+DROP TABLE weblog.contact_message;
+ALTER TABLE weblog.user DROP COLUMN is_admin;
@@ -0,0 +1,15 @@
+-- Add up migration script here
+ALTER TABLE weblog.user ADD COLUMN IF NOT EXISTS is_admin BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE TABLE IF NOT EXISTS weblog.contact_message (
+  id uuid PRIMARY KEY DEFAULT uuid_generate_v4(),
+  sender VARCHAR NOT NULL,
+  subject VARCHAR NOT NULL,
+  body TEXT NOT NULL,
+  ip_address VARCHAR,
+  delivery_status VARCHAR NOT NULL DEFAULT 'pending',
+  delivery_error TEXT,
+  created_at TIMESTAMP NOT NULL DEFAULT current_timestamp
+);
+
+CREATE INDEX ix_contact_message_created_at ON weblog.contact_message (created_at DESC);
@@ -0,0 +1,10 @@
+-- Add down migration script here
+ALTER TABLE weblog.contact_message ADD COLUMN delivery_status VARCHAR NOT NULL DEFAULT 'pending';
+ALTER TABLE weblog.contact_message ADD COLUMN delivery_error TEXT;
+UPDATE weblog.contact_message cm
+SET delivery_status = CASE mo.status WHEN 'sent' THEN 'sent' WHEN 'dead' THEN 'failed' ELSE 'pending' END,
+  delivery_error = mo.last_error
+FROM weblog.mail_outbox mo
+WHERE mo.id = cm.outbox_id;
+ALTER TABLE weblog.contact_message DROP COLUMN outbox_id;
+DROP TABLE weblog.mail_outbox;
@@ -0,0 +1,36 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS weblog.mail_outbox (
+  id uuid PRIMARY KEY DEFAULT uuid_generate_v4(),
+  kind VARCHAR NOT NULL,
+  envelope jsonb NOT NULL,
+  raw BYTEA NOT NULL,
+  status VARCHAR NOT NULL DEFAULT 'pending',
+  attempts INTEGER NOT NULL DEFAULT 0,
+  max_attempts INTEGER NOT NULL DEFAULT 8,
+  next_attempt_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT current_timestamp,
+  locked_at TIMESTAMP WITH TIME ZONE,
+  last_error TEXT,
+  created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT current_timestamp,
+  sent_at TIMESTAMP WITH TIME ZONE
+);
+
+CREATE INDEX ix_mail_outbox_due ON weblog.mail_outbox (next_attempt_at) WHERE status IN ('pending', 'sending');
+
+ALTER TABLE weblog.contact_message ADD COLUMN outbox_id uuid REFERENCES weblog.mail_outbox (id) ON DELETE SET NULL;
+
+-- Keep the delivery history of the messages sent before the outbox. Their raw message was
+-- never stored, so nothing is left pending for the worker to claim.
+INSERT INTO weblog.mail_outbox (id, kind, envelope, raw, status, attempts, next_attempt_at, last_error, created_at, sent_at)
+SELECT id, 'contact', '{}'::jsonb, ''::bytea,
+  CASE delivery_status WHEN 'sent' THEN 'sent' ELSE 'dead' END,
+  CASE delivery_status WHEN 'pending' THEN 0 ELSE 1 END,
+  created_at,
+  CASE delivery_status WHEN 'pending' THEN coalesce(delivery_error, 'never sent before the mail outbox') ELSE delivery_error END,
+  created_at,
+  CASE delivery_status WHEN 'sent' THEN created_at END
+FROM weblog.contact_message;
+
+UPDATE weblog.contact_message SET outbox_id = id;
+
+ALTER TABLE weblog.contact_message DROP COLUMN delivery_status;
+ALTER TABLE weblog.contact_message DROP COLUMN delivery_error;
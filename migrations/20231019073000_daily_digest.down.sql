@@ -0,0 +1,8 @@
+-- Add down migration script here
+DROP TABLE weblog.email_confirmation;
+DROP TABLE weblog.digest_log;
+DROP TABLE weblog.watchlist_symbol;
+DROP TABLE weblog.watchlist;
+ALTER TABLE weblog.user DROP COLUMN digest_opt_in;
+ALTER TABLE weblog.user DROP COLUMN language;
+ALTER TABLE weblog.user DROP COLUMN email;
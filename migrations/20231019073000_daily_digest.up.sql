@@ -0,0 +1,39 @@
+-- Add up migration script here
+ALTER TABLE weblog.user ADD COLUMN IF NOT EXISTS email VARCHAR;
+ALTER TABLE weblog.user ADD COLUMN IF NOT EXISTS language VARCHAR NOT NULL DEFAULT 'en';
+ALTER TABLE weblog.user ADD COLUMN IF NOT EXISTS digest_opt_in BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE TABLE IF NOT EXISTS weblog.watchlist (
+  id SERIAL PRIMARY KEY,
+  usr_id INTEGER NOT NULL REFERENCES weblog.user (usr_id) ON DELETE CASCADE,
+  name VARCHAR NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT current_timestamp,
+  updated_at TIMESTAMP NOT NULL DEFAULT current_timestamp,
+  UNIQUE (usr_id, name)
+);
+
+CREATE TABLE IF NOT EXISTS weblog.watchlist_symbol (
+  watchlist_id INTEGER NOT NULL REFERENCES weblog.watchlist (id) ON DELETE CASCADE,
+  symbol VARCHAR NOT NULL,
+  position INTEGER NOT NULL DEFAULT 0,
+  added_at TIMESTAMP NOT NULL DEFAULT current_timestamp,
+  PRIMARY KEY (watchlist_id, symbol)
+);
+
+CREATE TABLE IF NOT EXISTS weblog.digest_log (
+  usr_id INTEGER NOT NULL REFERENCES weblog.user (usr_id) ON DELETE CASCADE,
+  digest_date DATE NOT NULL,
+  outbox_id uuid REFERENCES weblog.mail_outbox (id) ON DELETE SET NULL,
+  PRIMARY KEY (usr_id, digest_date)
+);
+
+-- an address given for the digest is only stored on the user once its link is followed
+CREATE TABLE IF NOT EXISTS weblog.email_confirmation (
+  token uuid PRIMARY KEY DEFAULT uuid_generate_v4(),
+  usr_id INTEGER NOT NULL UNIQUE REFERENCES weblog.user (usr_id) ON DELETE CASCADE,
+  email VARCHAR NOT NULL,
+  digest_opt_in BOOLEAN NOT NULL,
+  outbox_id uuid REFERENCES weblog.mail_outbox (id) ON DELETE SET NULL,
+  expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+  created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT current_timestamp
+);
@@ -0,0 +1,72 @@
+-- Add up migration script here
+-- NYSE/NASDAQ full closures (early_close NULL) and half days (early_close set, New York time)
+CREATE TABLE IF NOT EXISTS demo_app.market_holiday (
+  holiday_date DATE PRIMARY KEY,
+  holiday_name VARCHAR NOT NULL,
+  early_close TIME
+);
+
+INSERT INTO demo_app.market_holiday (holiday_date, holiday_name, early_close) VALUES
+('2023-01-02', 'New Year''s Day (observed)', NULL),
+('2023-01-16', 'Martin Luther King, Jr. Day', NULL),
+('2023-02-20', 'Washington''s Birthday', NULL),
+('2023-04-07', 'Good Friday', NULL),
+('2023-05-29', 'Memorial Day', NULL),
+('2023-06-19', 'Juneteenth', NULL),
+('2023-07-03', 'Independence Day eve', '13:00'),
+('2023-07-04', 'Independence Day', NULL),
+('2023-09-04', 'Labor Day', NULL),
+('2023-11-23', 'Thanksgiving Day', NULL),
+('2023-11-24', 'Day after Thanksgiving', '13:00'),
+('2023-12-25', 'Christmas Day', NULL),
+('2024-01-01', 'New Year''s Day', NULL),
+('2024-01-15', 'Martin Luther King, Jr. Day', NULL),
+('2024-02-19', 'Washington''s Birthday', NULL),
+('2024-03-29', 'Good Friday', NULL),
+('2024-05-27', 'Memorial Day', NULL),
+('2024-06-19', 'Juneteenth', NULL),
+('2024-07-03', 'Independence Day eve', '13:00'),
+('2024-07-04', 'Independence Day', NULL),
+('2024-09-02', 'Labor Day', NULL),
+('2024-11-28', 'Thanksgiving Day', NULL),
+('2024-11-29', 'Day after Thanksgiving', '13:00'),
+('2024-12-24', 'Christmas Eve', '13:00'),
+('2024-12-25', 'Christmas Day', NULL),
+('2025-01-01', 'New Year''s Day', NULL),
+('2025-01-09', 'National Day of Mourning for Jimmy Carter', NULL),
+('2025-01-20', 'Martin Luther King, Jr. Day', NULL),
+('2025-02-17', 'Washington''s Birthday', NULL),
+('2025-04-18', 'Good Friday', NULL),
+('2025-05-26', 'Memorial Day', NULL),
+('2025-06-19', 'Juneteenth', NULL),
+('2025-07-03', 'Independence Day eve', '13:00'),
+('2025-07-04', 'Independence Day', NULL),
+('2025-09-01', 'Labor Day', NULL),
+('2025-11-27', 'Thanksgiving Day', NULL),
+('2025-11-28', 'Day after Thanksgiving', '13:00'),
+('2025-12-24', 'Christmas Eve', '13:00'),
+('2025-12-25', 'Christmas Day', NULL),
+('2026-01-01', 'New Year''s Day', NULL),
+('2026-01-19', 'Martin Luther King, Jr. Day', NULL),
+('2026-02-16', 'Washington''s Birthday', NULL),
+('2026-04-03', 'Good Friday', NULL),
+('2026-05-25', 'Memorial Day', NULL),
+('2026-06-19', 'Juneteenth', NULL),
+('2026-07-03', 'Independence Day (observed)', NULL),
+('2026-09-07', 'Labor Day', NULL),
+('2026-11-26', 'Thanksgiving Day', NULL),
+('2026-11-27', 'Day after Thanksgiving', '13:00'),
+('2026-12-24', 'Christmas Eve', '13:00'),
+('2026-12-25', 'Christmas Day', NULL),
+('2027-01-01', 'New Year''s Day', NULL),
+('2027-01-18', 'Martin Luther King, Jr. Day', NULL),
+('2027-02-15', 'Washington''s Birthday', NULL),
+('2027-03-26', 'Good Friday', NULL),
+('2027-05-31', 'Memorial Day', NULL),
+('2027-06-18', 'Juneteenth (observed)', NULL),
+('2027-07-05', 'Independence Day (observed)', NULL),
+('2027-09-06', 'Labor Day', NULL),
+('2027-11-25', 'Thanksgiving Day', NULL),
+('2027-11-26', 'Day after Thanksgiving', '13:00'),
+('2027-12-24', 'Christmas Day (observed)', NULL)
+ON CONFLICT DO NOTHING;
@@ -0,0 +1,56 @@
+-- Add down migration script here
+DROP MATERIALIZED VIEW minute_intraday_record;
+
+ALTER TABLE demo_app.stock_price ADD COLUMN ema FLOAT4 [] NULL, ADD COLUMN sma FLOAT4 [] NULL;
+
+INSERT INTO demo_app.stock_price (time, symbol, open, close, high, low, ema, sma, volume)
+SELECT db.date::timestamp, db.symbol, db.open, db.close, db.high, db.low,
+  ARRAY(
+    SELECT coalesce(di.value, 'NaN') FROM unnest(ARRAY[5, 20, 60]) WITH ORDINALITY AS p(period, ord)
+    LEFT JOIN demo_app.daily_indicator di ON di.symbol = db.symbol AND di.date = db.date
+      AND di.indicator = 'ema' AND di.params = jsonb_build_object('period', p.period)
+    ORDER BY p.ord
+  ),
+  ARRAY(
+    SELECT coalesce(di.value, 'NaN') FROM unnest(ARRAY[5, 20, 60]) WITH ORDINALITY AS p(period, ord)
+    LEFT JOIN demo_app.daily_indicator di ON di.symbol = db.symbol AND di.date = db.date
+      AND di.indicator = 'sma' AND di.params = jsonb_build_object('period', p.period)
+    ORDER BY p.ord
+  ),
+  db.volume
+FROM demo_app.daily_bar db;
+
+DROP TABLE demo_app.daily_indicator;
+DROP TABLE demo_app.daily_bar;
+
+-- daily rows are the ones with indicators, minute rows have none
+CREATE MATERIALIZED VIEW minute_intraday_record
+WITH (timescaledb.continuous, timescaledb.materialized_only = false) AS
+SELECT time_bucket(INTERVAL '1 minute', time) AS bucket, symbol,
+  first(open, time) AS open, max(high) AS high, min(low) AS low,
+  last(close, time) AS close, sum(volume) AS volume
+FROM demo_app.stock_price
+WHERE ema IS NULL
+GROUP BY bucket, symbol
+WITH NO DATA;
+
+SELECT add_continuous_aggregate_policy('minute_intraday_record',
+  start_offset => INTERVAL '3 days',
+  end_offset => INTERVAL '1 minute',
+  schedule_interval => INTERVAL '1 minute');
+
+CREATE MATERIALIZED VIEW daily_intraday_record
+WITH (timescaledb.continuous, timescaledb.materialized_only = false) AS
+SELECT time_bucket(INTERVAL '1 day', time) AS bucket, symbol,
+  first(open, time) AS open, max(high) AS high, min(low) AS low,
+  last(close, time) AS close, sum(volume) AS volume,
+  last(ema, time) AS ema, last(sma, time) AS sma
+FROM demo_app.stock_price
+WHERE ema IS NOT NULL
+GROUP BY bucket, symbol
+WITH NO DATA;
+
+SELECT add_continuous_aggregate_policy('daily_intraday_record',
+  start_offset => INTERVAL '1 month',
+  end_offset => INTERVAL '1 day',
+  schedule_interval => INTERVAL '1 hour');
@@ -0,0 +1,71 @@
+-- Add up migration script here
+-- daily bars and their indicators move out of stock_price, which keeps only minute rows
+CREATE TABLE IF NOT EXISTS demo_app.daily_bar (
+  symbol TEXT NOT NULL,
+  date DATE NOT NULL,
+  open FLOAT4 NOT NULL,
+  high FLOAT4 NOT NULL,
+  low FLOAT4 NOT NULL,
+  close FLOAT4 NOT NULL,
+  volume FLOAT4 NOT NULL,
+  PRIMARY KEY (symbol, date)
+);
+
+-- one row per value, e.g. ('ema', '{"period": 20}')
+CREATE TABLE IF NOT EXISTS demo_app.daily_indicator (
+  symbol TEXT NOT NULL,
+  date DATE NOT NULL,
+  indicator TEXT NOT NULL,
+  params JSONB NOT NULL DEFAULT '{}',
+  value FLOAT4 NOT NULL,
+  PRIMARY KEY (symbol, date, indicator, params),
+  FOREIGN KEY (symbol, date) REFERENCES demo_app.daily_bar (symbol, date) ON DELETE CASCADE
+);
+
+CREATE INDEX ix_daily_indicator_lookup ON demo_app.daily_indicator (symbol, indicator, params, date DESC);
+
+INSERT INTO demo_app.daily_bar (symbol, date, open, high, low, close, volume)
+SELECT DISTINCT ON (symbol, time::date) symbol, time::date, open, high, low, close, volume
+FROM demo_app.stock_price
+WHERE ema IS NOT NULL
+  AND open IS NOT NULL AND high IS NOT NULL AND low IS NOT NULL
+  AND close IS NOT NULL AND volume IS NOT NULL
+ORDER BY symbol, time::date, time DESC;
+
+-- array positions 1, 2, 3 held periods 5, 20, 60
+INSERT INTO demo_app.daily_indicator (symbol, date, indicator, params, value)
+SELECT DISTINCT ON (sp.symbol, sp.time::date, ind.indicator, p.period)
+  sp.symbol, sp.time::date, ind.indicator, jsonb_build_object('period', p.period), ind.value
+FROM demo_app.stock_price sp
+CROSS JOIN LATERAL (
+  SELECT 'ema' AS indicator, t.value, t.ord FROM unnest(sp.ema) WITH ORDINALITY AS t(value, ord)
+  UNION ALL
+  SELECT 'sma' AS indicator, t.value, t.ord FROM unnest(sp.sma) WITH ORDINALITY AS t(value, ord)
+) ind
+JOIN (VALUES (1, 5), (2, 20), (3, 60)) AS p(ord, period) ON p.ord = ind.ord
+JOIN demo_app.daily_bar db ON db.symbol = sp.symbol AND db.date = sp.time::date
+WHERE sp.ema IS NOT NULL AND ind.value IS NOT NULL AND ind.value <> 'NaN'
+ORDER BY sp.symbol, sp.time::date, ind.indicator, p.period, sp.time DESC;
+
+-- both continuous aggregates told the rows apart by `ema`. daily_bar replaces the daily one,
+-- the minute one is recreated below without the filter.
+DROP MATERIALIZED VIEW IF EXISTS daily_intraday_record;
+DROP MATERIALIZED VIEW IF EXISTS minute_intraday_record;
+
+DELETE FROM demo_app.stock_price WHERE ema IS NOT NULL;
+
+ALTER TABLE demo_app.stock_price DROP COLUMN ema, DROP COLUMN sma;
+
+CREATE MATERIALIZED VIEW minute_intraday_record
+WITH (timescaledb.continuous, timescaledb.materialized_only = false) AS
+SELECT time_bucket(INTERVAL '1 minute', time) AS bucket, symbol,
+  first(open, time) AS open, max(high) AS high, min(low) AS low,
+  last(close, time) AS close, sum(volume) AS volume
+FROM demo_app.stock_price
+GROUP BY bucket, symbol
+WITH NO DATA;
+
+SELECT add_continuous_aggregate_policy('minute_intraday_record',
+  start_offset => INTERVAL '3 days',
+  end_offset => INTERVAL '1 minute',
+  schedule_interval => INTERVAL '1 minute');
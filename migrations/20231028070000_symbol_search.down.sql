@@ -0,0 +1,6 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS demo_app.ix_stock_profile_cik_trgm;
+DROP INDEX IF EXISTS demo_app.ix_stock_profile_cusip_trgm;
+DROP INDEX IF EXISTS demo_app.ix_stock_profile_isin_trgm;
+DROP INDEX IF EXISTS demo_app.ix_stock_profile_company_name_trgm;
+DROP INDEX IF EXISTS demo_app.ix_stock_profile_symbol_trgm;
@@ -0,0 +1,9 @@
+-- Add up migration script here
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+-- trigram indexes back ILIKE prefix/contains lookups as well as the similarity operators
+CREATE INDEX IF NOT EXISTS ix_stock_profile_symbol_trgm ON demo_app.stock_profile USING gin (symbol gin_trgm_ops);
+CREATE INDEX IF NOT EXISTS ix_stock_profile_company_name_trgm ON demo_app.stock_profile USING gin (company_name gin_trgm_ops);
+CREATE INDEX IF NOT EXISTS ix_stock_profile_isin_trgm ON demo_app.stock_profile USING gin (isin gin_trgm_ops);
+CREATE INDEX IF NOT EXISTS ix_stock_profile_cusip_trgm ON demo_app.stock_profile USING gin (cusip gin_trgm_ops);
+CREATE INDEX IF NOT EXISTS ix_stock_profile_cik_trgm ON demo_app.stock_profile USING gin (cik gin_trgm_ops);
@@ -0,0 +1,3 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS weblog.ix_watchlist_symbol_position;
+ALTER TABLE weblog.watchlist DROP COLUMN IF EXISTS share_token;
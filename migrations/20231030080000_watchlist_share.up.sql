@@ -0,0 +1,5 @@
+-- Add up migration script here
+-- a set token makes the watchlist readable by anyone holding the link
+ALTER TABLE weblog.watchlist ADD COLUMN IF NOT EXISTS share_token uuid UNIQUE;
+
+CREATE INDEX IF NOT EXISTS ix_watchlist_symbol_position ON weblog.watchlist_symbol (watchlist_id, position);
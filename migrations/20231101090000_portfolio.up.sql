@@ -0,0 +1,25 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS weblog.portfolio (
+  id SERIAL PRIMARY KEY,
+  usr_id INTEGER NOT NULL REFERENCES weblog.user (usr_id) ON DELETE CASCADE,
+  name VARCHAR NOT NULL,
+  cost_method VARCHAR NOT NULL DEFAULT 'fifo' CHECK (cost_method IN ('fifo', 'average')),
+  created_at TIMESTAMP NOT NULL DEFAULT current_timestamp,
+  updated_at TIMESTAMP NOT NULL DEFAULT current_timestamp,
+  UNIQUE (usr_id, name)
+);
+
+-- a dividend is quantity * price, e.g. shares held times the amount per share
+CREATE TABLE IF NOT EXISTS weblog.portfolio_transaction (
+  id SERIAL PRIMARY KEY,
+  portfolio_id INTEGER NOT NULL REFERENCES weblog.portfolio (id) ON DELETE CASCADE,
+  symbol VARCHAR NOT NULL,
+  kind VARCHAR NOT NULL CHECK (kind IN ('buy', 'sell', 'dividend')),
+  trade_date DATE NOT NULL,
+  quantity FLOAT8 NOT NULL CHECK (quantity > 0),
+  price FLOAT8 NOT NULL CHECK (price >= 0),
+  fee FLOAT8 NOT NULL DEFAULT 0 CHECK (fee >= 0),
+  created_at TIMESTAMP NOT NULL DEFAULT current_timestamp
+);
+
+CREATE INDEX IF NOT EXISTS ix_portfolio_transaction_date ON weblog.portfolio_transaction (portfolio_id, trade_date, id);
@@ -0,0 +1,3 @@
+-- Add down migration script here
+DROP TABLE weblog.alert_trigger;
+DROP TABLE weblog.alert;
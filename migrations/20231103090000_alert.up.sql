@@ -0,0 +1,37 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS weblog.alert (
+  id SERIAL PRIMARY KEY,
+  usr_id INTEGER NOT NULL REFERENCES weblog.user (usr_id) ON DELETE CASCADE,
+  symbol VARCHAR NOT NULL,
+  kind VARCHAR NOT NULL CHECK (kind IN ('price', 'change', 'ema_cross', 'volume')),
+  direction VARCHAR NOT NULL DEFAULT 'above' CHECK (direction IN ('above', 'below')),
+  -- price level, percent change or multiple of vol_avg, unused by ema_cross
+  threshold FLOAT8,
+  cooldown_minutes INTEGER NOT NULL DEFAULT 1440 CHECK (cooldown_minutes >= 0),
+  notify_email BOOLEAN NOT NULL DEFAULT TRUE,
+  webhook_url VARCHAR,
+  webhook_secret VARCHAR NOT NULL,
+  active BOOLEAN NOT NULL DEFAULT TRUE,
+  -- whether the condition held at the last evaluation, null until the first one
+  last_state BOOLEAN,
+  last_evaluated_date DATE,
+  last_triggered_at TIMESTAMP WITH TIME ZONE,
+  created_at TIMESTAMP NOT NULL DEFAULT current_timestamp,
+  updated_at TIMESTAMP NOT NULL DEFAULT current_timestamp
+);
+
+CREATE INDEX IF NOT EXISTS ix_alert_active ON weblog.alert (symbol) WHERE active;
+
+CREATE TABLE IF NOT EXISTS weblog.alert_trigger (
+  id SERIAL PRIMARY KEY,
+  alert_id INTEGER NOT NULL REFERENCES weblog.alert (id) ON DELETE CASCADE,
+  bar_date DATE NOT NULL,
+  payload jsonb NOT NULL,
+  outbox_id uuid REFERENCES weblog.mail_outbox (id) ON DELETE SET NULL,
+  webhook_status VARCHAR CHECK (webhook_status IN ('pending', 'delivered', 'failed')),
+  webhook_attempts INTEGER NOT NULL DEFAULT 0,
+  webhook_error TEXT,
+  triggered_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT current_timestamp
+);
+
+CREATE INDEX IF NOT EXISTS ix_alert_trigger_alert ON weblog.alert_trigger (alert_id, triggered_at DESC);
@@ -0,0 +1,11 @@
+-- Add down migration script here
+DROP TABLE IF EXISTS demo_app.index_price;
+
+ALTER TABLE demo_app.indices
+  DROP COLUMN price,
+  DROP COLUMN change,
+  DROP COLUMN change_percent,
+  DROP COLUMN day_low,
+  DROP COLUMN day_high,
+  DROP COLUMN previous_close,
+  DROP COLUMN quoted_at;
@@ -0,0 +1,24 @@
+-- Add up migration script here
+-- latest quote of every index, refreshed by the daily routine
+ALTER TABLE demo_app.indices
+  ADD COLUMN price FLOAT8,
+  ADD COLUMN change FLOAT8,
+  ADD COLUMN change_percent FLOAT8,
+  ADD COLUMN day_low FLOAT8,
+  ADD COLUMN day_high FLOAT8,
+  ADD COLUMN previous_close FLOAT8,
+  ADD COLUMN quoted_at TIMESTAMPTZ;
+
+-- minute bars of the indices, apart from stock_price so stock queries never see them
+CREATE TABLE IF NOT EXISTS demo_app.index_price (
+  time TIMESTAMP NOT NULL,
+  symbol TEXT NOT NULL REFERENCES demo_app.indices (index_symbol) ON DELETE CASCADE,
+  open FLOAT4 NOT NULL,
+  high FLOAT4 NOT NULL,
+  low FLOAT4 NOT NULL,
+  close FLOAT4 NOT NULL,
+  volume FLOAT4 NOT NULL DEFAULT 0,
+  PRIMARY KEY (symbol, time)
+);
+
+SELECT create_hypertable('demo_app.index_price', 'time');
@@ -0,0 +1,7 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS weblog.ix_alert_trigger_webhook_due;
+UPDATE weblog.alert_trigger SET webhook_status = 'pending' WHERE webhook_status = 'sending';
+ALTER TABLE weblog.alert_trigger DROP COLUMN webhook_next_attempt_at, DROP COLUMN webhook_locked_at;
+ALTER TABLE weblog.alert_trigger DROP CONSTRAINT IF EXISTS alert_trigger_webhook_status_check;
+ALTER TABLE weblog.alert_trigger ADD CONSTRAINT alert_trigger_webhook_status_check
+  CHECK (webhook_status IN ('pending', 'delivered', 'failed'));
@@ -0,0 +1,11 @@
+-- Add up migration script here
+-- webhooks go out from a worker like mail_outbox, the routine only queues them
+ALTER TABLE weblog.alert_trigger DROP CONSTRAINT IF EXISTS alert_trigger_webhook_status_check;
+ALTER TABLE weblog.alert_trigger ADD CONSTRAINT alert_trigger_webhook_status_check
+  CHECK (webhook_status IN ('pending', 'sending', 'delivered', 'failed'));
+ALTER TABLE weblog.alert_trigger
+  ADD COLUMN webhook_next_attempt_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT current_timestamp,
+  ADD COLUMN webhook_locked_at TIMESTAMP WITH TIME ZONE;
+
+CREATE INDEX ix_alert_trigger_webhook_due ON weblog.alert_trigger (webhook_next_attempt_at)
+  WHERE webhook_status IN ('pending', 'sending');
@@ -1,4 +1,6 @@
 use crate::error::Result;
+use crate::mailer::Mailer;
+use crate::market_calendar::MarketCalendar;
 use axum::extract::FromRef;
 use oauth2::{basic::BasicClient, AuthUrl, ClientId, ClientSecret, RedirectUrl, TokenUrl};
 use redis::Client as RedisClient;
@@ -7,8 +9,10 @@ use sqlx::postgres::{PgPool, PgPoolOptions};
 use std::env;
 #[derive(Clone, Debug, FromRef)]
 pub struct AppState {
+    pub calendar: MarketCalendar,
     pub client: Client,
     pub db: PgPool,
+    pub mailer: Mailer,
     pub oauth_client: BasicClient,
     pub redis: RedisClient,
 }
@@ -55,13 +59,19 @@ impl AppState {
             }
         };
 
+        let calendar = MarketCalendar::load(&db).await?;
+
         let client = Client::new();
 
+        let mailer = Mailer::from_env()?;
+
         let redis_client = RedisClient::open("redis://127.0.0.1:6379").unwrap();
 
         Ok(Self {
+            calendar,
             client,
             db,
+            mailer,
             oauth_client,
             redis: redis_client,
         })
@@ -0,0 +1,190 @@
+use crate::error::{Error, Result};
+use crate::model::stock::ComparedSeries;
+use chrono::NaiveDateTime;
+use std::collections::{BTreeSet, HashMap};
+
+pub const MAX_SYMBOLS: usize = 10;
+
+/// `aapl, MSFT,AAPL` as `["AAPL", "MSFT"]`, in the order they were asked for.
+pub fn parse_symbols(raw: &str) -> Result<Vec<String>> {
+    let mut symbols: Vec<String> = vec![];
+    for symbol in raw.split(',').map(|t| t.trim().to_uppercase()) {
+        if !symbol.is_empty() && !symbols.contains(&symbol) {
+            symbols.push(symbol);
+        }
+    }
+
+    if symbols.is_empty() {
+        return Err(Error::BADREQUEST("symbols can not be empty.".to_string()));
+    }
+    if symbols.len() > MAX_SYMBOLS {
+        return Err(Error::BADREQUEST(format!(
+            "at most {MAX_SYMBOLS} symbols can be compared."
+        )));
+    }
+
+    Ok(symbols)
+}
+
+/// Puts the closes of every symbol on the union of their bucket times, carrying the last
+/// close forward over gaps, and drops the times before every symbol has a close.
+pub fn align(
+    symbols: &[String],
+    rows: &[(String, NaiveDateTime, f64)],
+    periods_per_year: f64,
+) -> Result<(Vec<NaiveDateTime>, Vec<ComparedSeries>)> {
+    let times: Vec<NaiveDateTime> = rows
+        .iter()
+        .map(|(_, time, _)| *time)
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+    let slot: HashMap<NaiveDateTime, usize> =
+        times.iter().enumerate().map(|(i, t)| (*t, i)).collect();
+    let column: HashMap<&str, usize> = symbols
+        .iter()
+        .enumerate()
+        .map(|(i, t)| (t.as_str(), i))
+        .collect();
+
+    let mut columns = vec![vec![None; times.len()]; symbols.len()];
+    for (symbol, time, close) in rows {
+        if let Some(&i) = column.get(symbol.as_str()) {
+            columns[i][slot[time]] = Some(*close);
+        }
+    }
+
+    let mut start = 0;
+    for (symbol, closes) in symbols.iter().zip(columns.iter_mut()) {
+        let Some(first) = closes.iter().position(Option::is_some) else {
+            return Err(Error::NOTFOUND(format!("symbol : `{symbol}`")));
+        };
+        start = start.max(first);
+
+        let mut last = None;
+        for close in closes.iter_mut() {
+            last = close.or(last);
+            *close = last;
+        }
+    }
+
+    let series = symbols
+        .iter()
+        .zip(columns)
+        .map(|(symbol, closes)| {
+            let closes: Vec<f64> = closes[start..].iter().flatten().copied().collect();
+            summarize(symbol, closes, periods_per_year)
+        })
+        .collect();
+
+    Ok((times[start..].to_vec(), series))
+}
+
+/// Total return and max drawdown in percent, volatility as the annualized sample standard
+/// deviation of the bucket to bucket returns, in percent. `closes` is never empty.
+fn summarize(symbol: &str, closes: Vec<f64>, periods_per_year: f64) -> ComparedSeries {
+    let base = closes[0];
+    let rebased: Vec<f64> = closes.iter().map(|t| (t / base - 1.0) * 100.0).collect();
+
+    let returns: Vec<f64> = closes.windows(2).map(|t| t[1] / t[0] - 1.0).collect();
+    let volatility = (returns.len() >= 2).then(|| {
+        let mean = returns.iter().sum::<f64>() / returns.len() as f64;
+        let variance =
+            returns.iter().map(|t| (t - mean).powi(2)).sum::<f64>() / (returns.len() - 1) as f64;
+        variance.sqrt() * periods_per_year.sqrt() * 100.0
+    });
+
+    let mut peak = base;
+    let mut max_drawdown: f64 = 0.0;
+    for close in &closes {
+        peak = peak.max(*close);
+        max_drawdown = max_drawdown.min((close / peak - 1.0) * 100.0);
+    }
+
+    ComparedSeries {
+        symbol: symbol.to_string(),
+        total_return: rebased[rebased.len() - 1],
+        closes,
+        rebased,
+        volatility,
+        max_drawdown,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn day(d: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2023, 10, d)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap()
+    }
+
+    fn row(symbol: &str, d: u32, close: f64) -> (String, NaiveDateTime, f64) {
+        (symbol.to_string(), day(d), close)
+    }
+
+    #[test]
+    fn parses_symbols() {
+        assert_eq!(
+            parse_symbols(" aapl, MSFT,,AAPL ").unwrap(),
+            vec!["AAPL".to_string(), "MSFT".to_string()]
+        );
+        assert!(parse_symbols(" , ").is_err());
+
+        let many: Vec<String> = (0..=MAX_SYMBOLS).map(|t| format!("S{t}")).collect();
+        assert!(parse_symbols(&many.join(",")).is_err());
+    }
+
+    #[test]
+    fn aligns_from_the_first_common_time_and_fills_gaps() {
+        let symbols = vec!["AAPL".to_string(), "NEW".to_string()];
+        let rows = vec![
+            row("AAPL", 2, 100.0),
+            row("AAPL", 3, 110.0),
+            row("NEW", 3, 20.0),
+            row("NEW", 4, 25.0),
+            row("AAPL", 5, 121.0),
+            row("NEW", 5, 30.0),
+        ];
+
+        let (times, series) = align(&symbols, &rows, 252.0).unwrap();
+
+        assert_eq!(times, vec![day(3), day(4), day(5)]);
+        assert_eq!(series[0].closes, vec![110.0, 110.0, 121.0]);
+        assert_eq!(series[1].closes, vec![20.0, 25.0, 30.0]);
+        assert_eq!(series[0].rebased[0], 0.0);
+        assert_eq!(series[1].rebased, vec![0.0, 25.0, 50.0]);
+        assert!((series[0].total_return - 10.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn summarizes_returns_volatility_and_drawdown() {
+        let series = summarize("AAPL", vec![100.0, 110.0, 99.0, 121.0], 252.0);
+
+        assert!((series.total_return - 21.0).abs() < 1e-9);
+        assert!((series.max_drawdown + 10.0).abs() < 1e-9);
+
+        // returns 0.1, -0.1, 0.2222..
+        let returns = [0.1, -0.1, 121.0 / 99.0 - 1.0];
+        let mean = returns.iter().sum::<f64>() / 3.0;
+        let sd = (returns.iter().map(|t| (t - mean).powi(2)).sum::<f64>() / 2.0).sqrt();
+        assert!((series.volatility.unwrap() - sd * 252f64.sqrt() * 100.0).abs() < 1e-9);
+
+        assert_eq!(
+            summarize("AAPL", vec![100.0, 101.0], 252.0).volatility,
+            None
+        );
+    }
+
+    #[test]
+    fn missing_symbol_is_not_found() {
+        let symbols = vec!["AAPL".to_string(), "NOPE".to_string()];
+        let err = align(&symbols, &[row("AAPL", 2, 100.0)], 252.0).unwrap_err();
+
+        assert!(matches!(err, Error::NOTFOUND(t) if t == "symbol : `NOPE`"));
+    }
+}
@@ -0,0 +1,296 @@
+use crate::app_state::AppState;
+use crate::error::{Error, Result};
+use crate::model::digest::{
+    CrossDirection, DigestQuote, DigestRecipient, DigestSymbol, EmaCross, Headline, WatchedSymbol,
+};
+use crate::outbox::{self, MailKind};
+use crate::routes::stock::{top_gainers, top_losers};
+use crate::routine::DAILY_PERIODS;
+use crate::template::{self, Lang};
+use chrono::Utc;
+use chrono_tz::Asia::Taipei;
+use lettre::{message::Mailbox, Message};
+use serde_json::json;
+use std::collections::HashMap;
+use tracing::error;
+
+const HEADLINES_PER_SYMBOL: i64 = 3;
+
+/// Queue one digest for every opted-in user who has not had today's yet.
+pub async fn send_daily_digest(app: AppState) -> Result<usize> {
+    let today = Utc::now().with_timezone(&Taipei).date_naive();
+
+    let recipients = sqlx::query_as::<_, DigestRecipient>(
+        "SELECT u.usr_id, u.username, u.email, u.language
+        FROM weblog.user u
+        WHERE u.digest_opt_in AND u.email IS NOT NULL
+            AND NOT EXISTS (
+                SELECT 1 FROM weblog.digest_log dl WHERE dl.usr_id = u.usr_id AND dl.digest_date = ($1)
+            )",
+    )
+    .bind(today)
+    .fetch_all(&app.db)
+    .await?;
+
+    if recipients.is_empty() {
+        return Ok(0);
+    }
+
+    let usr_ids: Vec<i32> = recipients.iter().map(|t| t.usr_id).collect();
+
+    let watched = sqlx::query_as::<_, WatchedSymbol>(
+        "SELECT w.usr_id, ws.symbol
+        FROM weblog.watchlist w
+        JOIN weblog.watchlist_symbol ws ON ws.watchlist_id = w.id
+        WHERE w.usr_id = ANY($1)
+        ORDER BY w.usr_id, w.id, ws.position",
+    )
+    .bind(&usr_ids)
+    .fetch_all(&app.db)
+    .await?;
+
+    let mut symbols: Vec<String> = watched.iter().map(|t| t.symbol.clone()).collect();
+    symbols.sort();
+    symbols.dedup();
+
+    let periods: Vec<i32> = DAILY_PERIODS.iter().map(|t| *t as i32).collect();
+
+    // a missing EMA comes back as NaN, which never counts as a cross
+    let quotes = sqlx::query_as::<_, DigestQuote>(
+        "with ranked as (
+            select symbol, date, close,
+                row_number() over (partition by symbol order by date desc) as rn
+            from demo_app.daily_bar
+            where symbol = ANY($1) and date >= current_date - 14
+        ),
+        ema as (
+            select r.symbol, r.rn,
+                array_agg(coalesce(di.value, 'NaN') order by p.ord) as ema
+            from ranked r
+            cross join unnest(($2)::int4[]) with ordinality as p(period, ord)
+            left join demo_app.daily_indicator di on di.symbol = r.symbol and di.date = r.date
+                and di.indicator = 'ema' and di.params = jsonb_build_object('period', p.period)
+            where r.rn <= 2
+            group by r.symbol, r.rn
+        )
+        select cur.symbol, sp.company_name, cur.close, prev.close as prev_close,
+            cur_ema.ema, prev_ema.ema as prev_ema
+        from ranked cur
+        join demo_app.stock_profile sp on sp.symbol = cur.symbol
+        join ema cur_ema on cur_ema.symbol = cur.symbol and cur_ema.rn = 1
+        left join ranked prev on prev.symbol = cur.symbol and prev.rn = 2
+        left join ema prev_ema on prev_ema.symbol = cur.symbol and prev_ema.rn = 2
+        where cur.rn = 1",
+    )
+    .bind(&symbols)
+    .bind(&periods)
+    .fetch_all(&app.db)
+    .await?;
+
+    let headlines = sqlx::query_as::<_, Headline>(
+        "select symbol, id, title, link, published_date
+        from (
+            select split_part(tickers, ':', 2) as symbol, id, title, link, published_date,
+                row_number() over (partition by split_part(tickers, ':', 2) order by published_date desc) as rn
+            from demo_app.fmp_news
+            where split_part(tickers, ':', 2) = ANY($1)
+        ) news
+        where rn <= ($2)
+        order by published_date desc",
+    )
+    .bind(&symbols)
+    .bind(HEADLINES_PER_SYMBOL)
+    .fetch_all(&app.db)
+    .await?;
+
+    let mut headlines_by_symbol: HashMap<String, Vec<Headline>> = HashMap::new();
+    for headline in headlines {
+        headlines_by_symbol
+            .entry(headline.symbol.clone())
+            .or_default()
+            .push(headline);
+    }
+
+    let digest_symbols: HashMap<String, DigestSymbol> = quotes
+        .into_iter()
+        .map(|quote| {
+            let headlines = headlines_by_symbol
+                .remove(&quote.symbol)
+                .unwrap_or_default();
+            (quote.symbol.clone(), digest_symbol(quote, headlines))
+        })
+        .collect();
+
+    let gainers = top_gainers(&app.db).await?;
+    let losers = top_losers(&app.db).await?;
+
+    let mut queued = 0;
+
+    for recipient in recipients {
+        let mut items: Vec<&DigestSymbol> = vec![];
+        for t in watched.iter().filter(|t| t.usr_id == recipient.usr_id) {
+            if let Some(item) = digest_symbols.get(&t.symbol) {
+                if !items.iter().any(|i| i.symbol == item.symbol) {
+                    items.push(item);
+                }
+            }
+        }
+
+        if items.is_empty() {
+            continue;
+        }
+
+        let Ok(to) = recipient.email.parse::<Mailbox>() else {
+            error!(
+                "digest: usr_id {} has an invalid email `{}`",
+                recipient.usr_id, recipient.email
+            );
+            continue;
+        };
+
+        let rendered = template::render(
+            "digest",
+            Lang::from_tag(&recipient.language),
+            json!({
+                "username": recipient.username,
+                "date": today.format("%Y-%m-%d").to_string(),
+                "symbols": items,
+                "gainers": gainers,
+                "losers": losers,
+            }),
+        )?;
+
+        let email = Message::builder()
+            .from(app.mailer.sender.clone())
+            .to(to)
+            .subject(rendered.subject.as_str())
+            .multipart(rendered.multipart())
+            .map_err(|e| Error::CUSTOMERROR(e.to_string()))?;
+
+        let mut transaction = app.db.begin().await?;
+
+        let logged = sqlx::query(
+            "INSERT INTO weblog.digest_log(usr_id, digest_date) VALUES ($1, $2)
+            ON CONFLICT DO NOTHING",
+        )
+        .bind(recipient.usr_id)
+        .bind(today)
+        .execute(&mut transaction)
+        .await?;
+
+        // another instance got to this user first
+        if logged.rows_affected() == 0 {
+            continue;
+        }
+
+        let outbox_id = outbox::enqueue(&mut transaction, MailKind::Digest, &email).await?;
+
+        sqlx::query(
+            "UPDATE weblog.digest_log SET outbox_id = ($1) WHERE usr_id = ($2) AND digest_date = ($3)",
+        )
+        .bind(outbox_id)
+        .bind(recipient.usr_id)
+        .bind(today)
+        .execute(&mut transaction)
+        .await?;
+
+        transaction.commit().await?;
+
+        queued += 1;
+    }
+
+    Ok(queued)
+}
+
+fn digest_symbol(quote: DigestQuote, headlines: Vec<Headline>) -> DigestSymbol {
+    let change = quote.prev_close.map(|prev| quote.close - prev);
+    let change_percent = quote
+        .prev_close
+        .filter(|prev| *prev != 0.0)
+        .map(|prev| (quote.close - prev) / prev * 100.0);
+
+    let ema_crosses = match &quote.prev_ema {
+        Some(prev) => ema_crosses(prev, &quote.ema),
+        None => vec![],
+    };
+
+    DigestSymbol {
+        symbol: quote.symbol,
+        company_name: quote.company_name,
+        close: quote.close,
+        change,
+        change_percent,
+        ema_crosses,
+        headlines,
+    }
+}
+
+/// EMA 5/20 and 20/60 crossovers between two consecutive daily bars, given in `DAILY_PERIODS` order.
+pub fn ema_crosses(prev: &[f32], cur: &[f32]) -> Vec<EmaCross> {
+    let mut crosses = vec![];
+
+    for (fast, slow) in [(0, 1), (1, 2)] {
+        let (Some(prev_fast), Some(prev_slow), Some(cur_fast), Some(cur_slow)) =
+            (prev.get(fast), prev.get(slow), cur.get(fast), cur.get(slow))
+        else {
+            continue;
+        };
+
+        let direction = if prev_fast <= prev_slow && cur_fast > cur_slow {
+            CrossDirection::Above
+        } else if prev_fast >= prev_slow && cur_fast < cur_slow {
+            CrossDirection::Below
+        } else {
+            continue;
+        };
+
+        crosses.push(EmaCross {
+            fast: DAILY_PERIODS[fast] as u32,
+            slow: DAILY_PERIODS[slow] as u32,
+            direction,
+        });
+    }
+
+    crosses
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_crosses_in_both_directions() {
+        let crosses = ema_crosses(&[9.0, 10.0, 11.0], &[10.5, 10.0, 9.5]);
+        assert_eq!(
+            crosses,
+            vec![
+                EmaCross {
+                    fast: 5,
+                    slow: 20,
+                    direction: CrossDirection::Above
+                },
+                EmaCross {
+                    fast: 20,
+                    slow: 60,
+                    direction: CrossDirection::Above
+                },
+            ]
+        );
+
+        let crosses = ema_crosses(&[10.5, 10.0, 9.0], &[9.5, 10.0, 9.0]);
+        assert_eq!(
+            crosses,
+            vec![EmaCross {
+                fast: 5,
+                slow: 20,
+                direction: CrossDirection::Below
+            }]
+        );
+    }
+
+    #[test]
+    fn no_cross_without_a_sign_change() {
+        assert!(ema_crosses(&[11.0, 10.0, 9.0], &[12.0, 10.5, 9.1]).is_empty());
+        assert!(ema_crosses(&[], &[1.0, 2.0, 3.0]).is_empty());
+    }
+}
@@ -5,9 +5,10 @@ use axum::{
     Error as AxumError,
 };
 use cron::error::Error as CronError;
-use lettre::error::Error as LettreError;
+use redis::RedisError;
 use serde_json::json;
 use std::env::VarError;
+use validator::ValidationErrors;
 pub type Result<T> = core::result::Result<T, Error>;
 
 // pub struct Error;
@@ -25,7 +26,7 @@ pub enum Error {
     SystemLoadParametersError(#[from] VarError),
 
     #[error("{0}")]
-    LettreFail(#[from] LettreError),
+    LettreFail(String),
 
     #[error("{0}")]
     SystemAxumError(#[from] AxumError),
@@ -33,12 +34,30 @@ pub enum Error {
     #[error("{0}")]
     SystemCronError(#[from] CronError),
 
+    #[error("{0}")]
+    SystemRedisError(#[from] RedisError),
+
+    #[error("{0}")]
+    SystemTemplateError(#[from] minijinja::Error),
+
+    #[error("Invalid fields: {0}")]
+    VALIDATIONERROR(#[from] ValidationErrors),
+
     #[error("{0} is Not Found.")]
     NOTFOUND(String),
 
     #[error("{0}")]
     BADREQUEST(String),
 
+    #[error("{0}")]
+    UNAUTHORIZED(String),
+
+    #[error("{0}")]
+    FORBIDDEN(String),
+
+    #[error("{0}")]
+    TOOMANYREQUESTS(String),
+
     #[error("{0}")]
     CUSTOMERROR(String),
 }
@@ -47,6 +66,10 @@ impl Error {
     fn get_code(&self) -> (StatusCode, u16) {
         match *self {
             Error::BADREQUEST(_) => (StatusCode::BAD_REQUEST, 400),
+            Error::VALIDATIONERROR(_) => (StatusCode::BAD_REQUEST, 400),
+            Error::UNAUTHORIZED(_) => (StatusCode::UNAUTHORIZED, 401),
+            Error::FORBIDDEN(_) => (StatusCode::FORBIDDEN, 403),
+            Error::TOOMANYREQUESTS(_) => (StatusCode::TOO_MANY_REQUESTS, 429),
             Error::NOTFOUND(_) => (StatusCode::NOT_FOUND, 404),
             // Error::SectError(_) => (StatusCode::NOT_FOUND, 404),
             Error::LettreFail(_) => (StatusCode::BAD_GATEWAY, 502),
@@ -59,7 +82,12 @@ impl IntoResponse for Error {
     fn into_response(self) -> Response {
         let (status_code, code) = self.get_code();
         let message = self.to_string();
-        let body = Json(json!({ "status_code": code, "message":message }));
+        let body = match self {
+            Error::VALIDATIONERROR(errors) => Json(
+                json!({ "status_code": code, "message":message, "errors": errors.field_errors() }),
+            ),
+            _ => Json(json!({ "status_code": code, "message":message })),
+        };
 
         (status_code, body).into_response()
     }
@@ -0,0 +1,475 @@
+use crate::error::{Error, Result};
+use serde::Serialize;
+
+// Every indicator returns one entry per input bar, `None` until it has seen enough bars.
+
+pub const MACD_FAST: usize = 12;
+pub const MACD_SLOW: usize = 26;
+pub const MACD_SIGNAL: usize = 9;
+pub const BOLLINGER_WIDTH: f64 = 2.0;
+pub const STOCHASTIC_D: usize = 3;
+pub const MAX_PERIOD: usize = 250;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IndicatorKind {
+    Sma,
+    Ema,
+    Rsi,
+    Macd,
+    Bollinger,
+    Atr,
+    Stochastic,
+}
+
+impl IndicatorKind {
+    pub const ALL: [&'static str; 7] = [
+        "sma",
+        "ema",
+        "rsi",
+        "macd",
+        "bollinger",
+        "atr",
+        "stochastic",
+    ];
+
+    pub fn default_period(&self) -> usize {
+        match self {
+            IndicatorKind::Sma | IndicatorKind::Ema | IndicatorKind::Bollinger => 20,
+            IndicatorKind::Rsi | IndicatorKind::Atr | IndicatorKind::Stochastic => 14,
+            IndicatorKind::Macd => MACD_SLOW,
+        }
+    }
+
+    /// Bars to load ahead of the first wanted value. The smoothed indicators get a few
+    /// periods more so the seed has washed out.
+    pub fn lookback(&self, period: usize) -> usize {
+        match self {
+            IndicatorKind::Sma | IndicatorKind::Bollinger => period - 1,
+            IndicatorKind::Stochastic => period + STOCHASTIC_D - 2,
+            IndicatorKind::Ema | IndicatorKind::Rsi | IndicatorKind::Atr => period * 4,
+            IndicatorKind::Macd => MACD_SLOW * 4 + MACD_SIGNAL,
+        }
+    }
+}
+
+impl std::str::FromStr for IndicatorKind {
+    type Err = Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "sma" => Ok(IndicatorKind::Sma),
+            "ema" => Ok(IndicatorKind::Ema),
+            "rsi" => Ok(IndicatorKind::Rsi),
+            "macd" => Ok(IndicatorKind::Macd),
+            "bollinger" => Ok(IndicatorKind::Bollinger),
+            "atr" => Ok(IndicatorKind::Atr),
+            "stochastic" => Ok(IndicatorKind::Stochastic),
+            _ => Err(Error::BADREQUEST(format!(
+                "type must be one of {}.",
+                IndicatorKind::ALL.join(", ")
+            ))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum IndicatorValue {
+    Single(f64),
+    Macd {
+        macd: f64,
+        signal: f64,
+        histogram: f64,
+    },
+    Bands {
+        upper: f64,
+        middle: f64,
+        lower: f64,
+    },
+    Stochastic {
+        k: f64,
+        d: f64,
+    },
+}
+
+/// Price columns of consecutive daily bars, oldest first.
+pub struct Series<'a> {
+    pub high: &'a [f64],
+    pub low: &'a [f64],
+    pub close: &'a [f64],
+}
+
+pub fn check_period(period: usize) -> Result<()> {
+    if period == 0 || period > MAX_PERIOD {
+        return Err(Error::BADREQUEST(format!(
+            "period must be between 1 and {MAX_PERIOD}."
+        )));
+    }
+
+    Ok(())
+}
+
+pub fn compute(
+    kind: IndicatorKind,
+    period: usize,
+    series: &Series,
+) -> Result<Vec<Option<IndicatorValue>>> {
+    check_period(period)?;
+
+    let single = |values: Vec<Option<f64>>| {
+        values
+            .into_iter()
+            .map(|t| t.map(IndicatorValue::Single))
+            .collect()
+    };
+
+    let values = match kind {
+        IndicatorKind::Sma => single(sma(series.close, period)),
+        IndicatorKind::Ema => single(ema(series.close, period)),
+        IndicatorKind::Rsi => single(rsi(series.close, period)),
+        IndicatorKind::Atr => single(atr(series.high, series.low, series.close, period)),
+        IndicatorKind::Macd => macd(series.close, MACD_FAST, MACD_SLOW, MACD_SIGNAL),
+        IndicatorKind::Bollinger => bollinger(series.close, period, BOLLINGER_WIDTH),
+        IndicatorKind::Stochastic => {
+            stochastic(series.high, series.low, series.close, period, STOCHASTIC_D)
+        }
+    };
+
+    Ok(values)
+}
+
+pub fn sma(values: &[f64], period: usize) -> Vec<Option<f64>> {
+    let mut out = vec![None; values.len()];
+    if period == 0 {
+        return out;
+    }
+
+    for (i, window) in values.windows(period).enumerate() {
+        out[i + period - 1] = Some(window.iter().sum::<f64>() / period as f64);
+    }
+
+    out
+}
+
+/// Seeded with the SMA of the first `period` values.
+pub fn ema(values: &[f64], period: usize) -> Vec<Option<f64>> {
+    let mut out = vec![None; values.len()];
+    if period == 0 || values.len() < period {
+        return out;
+    }
+
+    let k = 2.0 / (period as f64 + 1.0);
+    let mut prev = values[..period].iter().sum::<f64>() / period as f64;
+    out[period - 1] = Some(prev);
+
+    for (value, slot) in values.iter().zip(out.iter_mut()).skip(period) {
+        prev += (value - prev) * k;
+        *slot = Some(prev);
+    }
+
+    out
+}
+
+/// Wilder's RSI: simple averages over the first `period` changes, smoothed after that.
+pub fn rsi(closes: &[f64], period: usize) -> Vec<Option<f64>> {
+    let mut out = vec![None; closes.len()];
+    if period == 0 || closes.len() <= period {
+        return out;
+    }
+
+    let changes: Vec<f64> = closes.windows(2).map(|t| t[1] - t[0]).collect();
+
+    let mut gain = changes[..period].iter().map(|t| t.max(0.0)).sum::<f64>() / period as f64;
+    let mut loss = changes[..period].iter().map(|t| (-t).max(0.0)).sum::<f64>() / period as f64;
+    out[period] = Some(rsi_value(gain, loss));
+
+    let p = period as f64;
+    for (i, change) in changes.iter().enumerate().skip(period) {
+        gain = (gain * (p - 1.0) + change.max(0.0)) / p;
+        loss = (loss * (p - 1.0) + (-change).max(0.0)) / p;
+        out[i + 1] = Some(rsi_value(gain, loss));
+    }
+
+    out
+}
+
+fn rsi_value(gain: f64, loss: f64) -> f64 {
+    if loss == 0.0 {
+        100.0
+    } else {
+        100.0 - 100.0 / (1.0 + gain / loss)
+    }
+}
+
+pub fn macd(
+    closes: &[f64],
+    fast: usize,
+    slow: usize,
+    signal: usize,
+) -> Vec<Option<IndicatorValue>> {
+    let fast = ema(closes, fast);
+    let slow = ema(closes, slow);
+
+    let line: Vec<Option<f64>> = fast
+        .iter()
+        .zip(&slow)
+        .map(|(f, s)| Some((*f)? - (*s)?))
+        .collect();
+    let signal = on_tail(&line, |t| ema(t, signal));
+
+    line.iter()
+        .zip(&signal)
+        .map(|(m, s)| {
+            let (macd, signal) = ((*m)?, (*s)?);
+            Some(IndicatorValue::Macd {
+                macd,
+                signal,
+                histogram: macd - signal,
+            })
+        })
+        .collect()
+}
+
+/// Middle band is the SMA, the others are `width` population standard deviations away.
+pub fn bollinger(closes: &[f64], period: usize, width: f64) -> Vec<Option<IndicatorValue>> {
+    let mut out = vec![None; closes.len()];
+    if period == 0 {
+        return out;
+    }
+
+    for (i, window) in closes.windows(period).enumerate() {
+        let mean = window.iter().sum::<f64>() / period as f64;
+        let variance = window.iter().map(|t| (t - mean).powi(2)).sum::<f64>() / period as f64;
+        let sd = variance.sqrt();
+
+        out[i + period - 1] = Some(IndicatorValue::Bands {
+            upper: mean + width * sd,
+            middle: mean,
+            lower: mean - width * sd,
+        });
+    }
+
+    out
+}
+
+/// Wilder's ATR. The first true range is high - low, there is no close before it.
+pub fn atr(high: &[f64], low: &[f64], close: &[f64], period: usize) -> Vec<Option<f64>> {
+    let len = high.len().min(low.len()).min(close.len());
+    let mut out = vec![None; len];
+    if period == 0 || len < period {
+        return out;
+    }
+
+    let true_range: Vec<f64> = (0..len)
+        .map(|i| {
+            let range = high[i] - low[i];
+            match i.checked_sub(1).map(|t| close[t]) {
+                Some(prev) => range.max((high[i] - prev).abs()).max((low[i] - prev).abs()),
+                None => range,
+            }
+        })
+        .collect();
+
+    let p = period as f64;
+    let mut prev = true_range[..period].iter().sum::<f64>() / p;
+    out[period - 1] = Some(prev);
+
+    for (tr, slot) in true_range.iter().zip(out.iter_mut()).skip(period) {
+        prev = (prev * (p - 1.0) + tr) / p;
+        *slot = Some(prev);
+    }
+
+    out
+}
+
+/// %K over `k_period` bars with %D as its `d_period` SMA.
+pub fn stochastic(
+    high: &[f64],
+    low: &[f64],
+    close: &[f64],
+    k_period: usize,
+    d_period: usize,
+) -> Vec<Option<IndicatorValue>> {
+    let len = high.len().min(low.len()).min(close.len());
+    let mut k = vec![None; len];
+    if k_period == 0 || len < k_period {
+        return vec![None; len];
+    }
+
+    for i in (k_period - 1)..len {
+        let from = i + 1 - k_period;
+        let highest = high[from..=i].iter().copied().fold(f64::MIN, f64::max);
+        let lowest = low[from..=i].iter().copied().fold(f64::MAX, f64::min);
+
+        // a flat range has no position inside it, put it in the middle
+        k[i] = Some(if highest == lowest {
+            50.0
+        } else {
+            (close[i] - lowest) / (highest - lowest) * 100.0
+        });
+    }
+
+    let d = on_tail(&k, |t| sma(t, d_period));
+
+    k.iter()
+        .zip(&d)
+        .map(|(k, d)| Some(IndicatorValue::Stochastic { k: (*k)?, d: (*d)? }))
+        .collect()
+}
+
+/// Run `f` over the values after the leading `None`s, keeping the output aligned.
+fn on_tail(values: &[Option<f64>], f: impl Fn(&[f64]) -> Vec<Option<f64>>) -> Vec<Option<f64>> {
+    let start = values
+        .iter()
+        .position(Option::is_some)
+        .unwrap_or(values.len());
+    let tail: Vec<f64> = values[start..].iter().map_while(|t| *t).collect();
+
+    let mut out = vec![None; start];
+    out.extend(f(&tail));
+    out.resize(values.len(), None);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: Option<f64>, expected: f64, tolerance: f64) {
+        let actual = actual.expect("value should be ready");
+        assert!(
+            (actual - expected).abs() <= tolerance,
+            "expected {expected}, got {actual}"
+        );
+    }
+
+    // closes from the StockCharts RSI walkthrough
+    const CLOSES: [f64; 20] = [
+        44.34, 44.09, 44.15, 43.61, 44.33, 44.83, 45.10, 45.42, 45.84, 46.08, 45.89, 46.03, 45.61,
+        46.28, 46.28, 46.00, 46.03, 46.41, 46.22, 45.64,
+    ];
+
+    #[test]
+    fn sma_and_ema() {
+        let values = [
+            22.27, 22.19, 22.08, 22.17, 22.18, 22.13, 22.23, 22.43, 22.24, 22.29, 22.15,
+        ];
+
+        let sma = sma(&values, 10);
+        assert_eq!(sma[8], None);
+        assert_close(sma[9], 22.221, 1e-9);
+        assert_close(sma[10], 22.209, 1e-9);
+
+        let ema = ema(&values, 10);
+        assert_eq!(ema[8], None);
+        assert_close(ema[9], 22.221, 1e-9);
+        assert_close(ema[10], 22.20809090909091, 1e-9);
+    }
+
+    #[test]
+    fn rsi_matches_wilder() {
+        let rsi = rsi(&CLOSES, 14);
+        assert_eq!(rsi[13], None);
+        // the walkthrough rounds its averages to 2 places and shows 70.53, 66.32, ...
+        // these are the unrounded values TA-Lib gives for the same closes
+        assert_close(rsi[14], 70.46, 0.01);
+        assert_close(rsi[15], 66.25, 0.01);
+        assert_close(rsi[16], 66.48, 0.01);
+        assert_close(rsi[17], 69.35, 0.01);
+        assert_close(rsi[18], 66.29, 0.01);
+        assert_close(rsi[19], 57.92, 0.01);
+    }
+
+    #[test]
+    fn rsi_without_losses_is_100() {
+        let rsi = rsi(&[1.0, 2.0, 3.0, 4.0], 2);
+        assert_close(rsi[3], 100.0, 1e-9);
+    }
+
+    #[test]
+    fn macd_is_the_ema_spread() {
+        let closes: Vec<f64> = (0..40)
+            .map(|t| 100.0 + (t as f64 * 0.7).sin() * 5.0)
+            .collect();
+        let values = macd(&closes, 3, 6, 4);
+        let fast = ema(&closes, 3);
+        let slow = ema(&closes, 6);
+
+        // the signal needs 4 MACD values, the first of which lands on bar 5
+        assert_eq!(values[7], None);
+        let Some(IndicatorValue::Macd {
+            macd,
+            signal,
+            histogram,
+        }) = values[8]
+        else {
+            panic!("macd should be ready on bar 8");
+        };
+
+        let line: Vec<f64> = (5..=8)
+            .map(|i| fast[i].unwrap() - slow[i].unwrap())
+            .collect();
+        assert_close(Some(macd), line[3], 1e-9);
+        assert_close(Some(signal), line.iter().sum::<f64>() / 4.0, 1e-9);
+        assert_close(Some(histogram), macd - signal, 1e-9);
+    }
+
+    #[test]
+    fn bollinger_bands() {
+        let values = bollinger(&[2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0], 8, 2.0);
+        assert_eq!(
+            values[7],
+            Some(IndicatorValue::Bands {
+                upper: 9.0,
+                middle: 5.0,
+                lower: 1.0,
+            })
+        );
+    }
+
+    #[test]
+    fn atr_uses_wilder_smoothing() {
+        let high = [48.70, 48.72, 48.90, 48.87, 48.82];
+        let low = [47.79, 48.14, 48.39, 48.37, 48.24];
+        let close = [48.16, 48.61, 48.75, 48.63, 48.74];
+
+        let atr = atr(&high, &low, &close, 3);
+        assert_eq!(atr[1], None);
+        // true ranges 0.91, 0.58, 0.51, 0.50, 0.58
+        assert_close(atr[2], (0.91 + 0.58 + 0.51) / 3.0, 1e-9);
+        assert_close(atr[3], (atr[2].unwrap() * 2.0 + 0.50) / 3.0, 1e-9);
+        assert_close(atr[4], (atr[3].unwrap() * 2.0 + 0.58) / 3.0, 1e-9);
+    }
+
+    #[test]
+    fn stochastic_k_and_d() {
+        let high = [10.0, 12.0, 11.0, 13.0, 14.0];
+        let low = [8.0, 9.0, 9.0, 10.0, 12.0];
+        let close = [9.0, 11.0, 10.0, 12.0, 13.0];
+
+        let values = stochastic(&high, &low, &close, 3, 2);
+        assert_eq!(values[2], None);
+        // %K: bar 2 = 50, bar 3 = 75, bar 4 = 80
+        assert_eq!(
+            values[3],
+            Some(IndicatorValue::Stochastic { k: 75.0, d: 62.5 })
+        );
+        assert_eq!(
+            values[4],
+            Some(IndicatorValue::Stochastic { k: 80.0, d: 77.5 })
+        );
+    }
+
+    #[test]
+    fn rejects_out_of_range_periods() {
+        let series = Series {
+            high: &CLOSES,
+            low: &CLOSES,
+            close: &CLOSES,
+        };
+        assert!(compute(IndicatorKind::Sma, 0, &series).is_err());
+        assert!(compute(IndicatorKind::Sma, MAX_PERIOD + 1, &series).is_err());
+        assert!(compute(IndicatorKind::Sma, 5, &series).is_ok());
+    }
+}
@@ -0,0 +1,500 @@
+use crate::error::{Error, Result};
+use crate::model::portfolio::{NewTransaction, PortfolioTransaction, PortfolioValue};
+use chrono::{NaiveDate, Utc};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+// what is left of a position after selling all of it in several parts
+const EPSILON: f64 = 1e-9;
+pub const MAX_IMPORT_ROWS: usize = 5_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CostMethod {
+    Fifo,
+    Average,
+}
+
+impl CostMethod {
+    pub const ALL: [&'static str; 2] = ["fifo", "average"];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CostMethod::Fifo => "fifo",
+            CostMethod::Average => "average",
+        }
+    }
+}
+
+impl std::str::FromStr for CostMethod {
+    type Err = Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "fifo" => Ok(CostMethod::Fifo),
+            "average" => Ok(CostMethod::Average),
+            _ => Err(Error::BADREQUEST(format!(
+                "cost_method must be one of {}.",
+                CostMethod::ALL.join(", ")
+            ))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransactionKind {
+    Buy,
+    Sell,
+    Dividend,
+}
+
+impl TransactionKind {
+    pub const ALL: [&'static str; 3] = ["buy", "sell", "dividend"];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TransactionKind::Buy => "buy",
+            TransactionKind::Sell => "sell",
+            TransactionKind::Dividend => "dividend",
+        }
+    }
+}
+
+impl std::str::FromStr for TransactionKind {
+    type Err = Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "buy" => Ok(TransactionKind::Buy),
+            "sell" => Ok(TransactionKind::Sell),
+            "dividend" => Ok(TransactionKind::Dividend),
+            _ => Err(Error::BADREQUEST(format!(
+                "kind must be one of {}.",
+                TransactionKind::ALL.join(", ")
+            ))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Position {
+    pub symbol: String,
+    pub quantity: f64,
+    pub cost_basis: f64,
+    pub realized: f64,
+    pub dividends: f64,
+}
+
+#[derive(Debug, Default)]
+struct Lots {
+    // (quantity, cost per share including the buying fee), oldest first. Only kept for FIFO.
+    open: VecDeque<(f64, f64)>,
+    quantity: f64,
+    cost: f64,
+    realized: f64,
+    dividends: f64,
+}
+
+/// Positions built up by applying transactions in trade order.
+#[derive(Debug)]
+pub struct Book {
+    method: CostMethod,
+    symbols: BTreeMap<String, Lots>,
+}
+
+impl Book {
+    pub fn new(method: CostMethod) -> Self {
+        Self {
+            method,
+            symbols: BTreeMap::new(),
+        }
+    }
+
+    pub fn apply(&mut self, tx: &PortfolioTransaction) -> Result<()> {
+        let kind = tx.kind.parse::<TransactionKind>()?;
+        let lots = self.symbols.entry(tx.symbol.clone()).or_default();
+
+        match kind {
+            TransactionKind::Buy => {
+                let cost = tx.quantity * tx.price + tx.fee;
+                lots.quantity += tx.quantity;
+                lots.cost += cost;
+                if self.method == CostMethod::Fifo {
+                    lots.open.push_back((tx.quantity, cost / tx.quantity));
+                }
+            }
+            TransactionKind::Sell => {
+                if tx.quantity > lots.quantity + EPSILON {
+                    return Err(Error::BADREQUEST(format!(
+                        "selling {} {} on {} exceeds the {} held.",
+                        tx.quantity, tx.symbol, tx.trade_date, lots.quantity
+                    )));
+                }
+
+                let sold_cost = match self.method {
+                    CostMethod::Average => lots.cost * (tx.quantity / lots.quantity).min(1.0),
+                    CostMethod::Fifo => {
+                        let mut left = tx.quantity;
+                        let mut cost = 0.0;
+                        while left > EPSILON {
+                            let Some(lot) = lots.open.front_mut() else {
+                                break;
+                            };
+                            let take = left.min(lot.0);
+                            cost += take * lot.1;
+                            lot.0 -= take;
+                            left -= take;
+                            if lot.0 <= EPSILON {
+                                lots.open.pop_front();
+                            }
+                        }
+                        cost
+                    }
+                };
+
+                lots.quantity -= tx.quantity;
+                lots.cost -= sold_cost;
+                lots.realized += tx.quantity * tx.price - tx.fee - sold_cost;
+
+                if lots.quantity <= EPSILON {
+                    lots.quantity = 0.0;
+                    lots.cost = 0.0;
+                    lots.open.clear();
+                }
+            }
+            TransactionKind::Dividend => lots.dividends += tx.quantity * tx.price - tx.fee,
+        }
+
+        Ok(())
+    }
+
+    /// Every symbol ever traded, closed positions included for their realized P&L.
+    pub fn positions(&self) -> Vec<Position> {
+        self.symbols
+            .iter()
+            .map(|(symbol, lots)| Position {
+                symbol: symbol.clone(),
+                quantity: lots.quantity,
+                cost_basis: lots.cost,
+                realized: lots.realized,
+                dividends: lots.dividends,
+            })
+            .collect()
+    }
+}
+
+/// Replays `transactions`, which must be in (trade_date, id) order. Fails on the first sell
+/// of more shares than were held at the time.
+pub fn replay(method: CostMethod, transactions: &[PortfolioTransaction]) -> Result<Book> {
+    let mut book = Book::new(method);
+    for tx in transactions {
+        book.apply(tx)?;
+    }
+    Ok(book)
+}
+
+/// Market value and cost basis at the end of each of `dates`. `closes` holds each symbol's
+/// closes in date order, starting before the first date so prices can be carried forward.
+/// Positions without any close yet count at cost.
+pub fn history(
+    method: CostMethod,
+    transactions: &[PortfolioTransaction],
+    dates: &[NaiveDate],
+    closes: &HashMap<String, Vec<(NaiveDate, f64)>>,
+) -> Result<Vec<PortfolioValue>> {
+    let mut book = Book::new(method);
+    let mut pending = transactions.iter().peekable();
+    let mut cursor: HashMap<String, usize> = HashMap::new();
+    let mut values = Vec::with_capacity(dates.len());
+
+    for &date in dates {
+        while let Some(tx) = pending.next_if(|t| t.trade_date <= date) {
+            book.apply(tx)?;
+        }
+
+        let mut market_value = 0.0;
+        let mut cost_basis = 0.0;
+        for (symbol, lots) in book.symbols.iter().filter(|(_, t)| t.quantity > 0.0) {
+            cost_basis += lots.cost;
+
+            let series = closes.get(symbol).map(Vec::as_slice).unwrap_or(&[]);
+            let at = cursor.entry(symbol.clone()).or_insert(0);
+            while *at < series.len() && series[*at].0 <= date {
+                *at += 1;
+            }
+
+            market_value += match *at {
+                0 => lots.cost,
+                n => lots.quantity * series[n - 1].1,
+            };
+        }
+
+        values.push(PortfolioValue {
+            date,
+            market_value,
+            cost_basis,
+        });
+    }
+
+    Ok(values)
+}
+
+/// Rejects what the database constraints would, with a message a person can act on.
+pub fn check(tx: &NewTransaction) -> Result<TransactionKind> {
+    let kind = tx.kind.trim().to_lowercase().parse::<TransactionKind>()?;
+
+    if tx.symbol.trim().is_empty() {
+        return Err(Error::BADREQUEST("symbol can not be empty.".to_string()));
+    }
+    if !tx.quantity.is_finite() || tx.quantity <= 0.0 {
+        return Err(Error::BADREQUEST(
+            "quantity must be greater than 0.".to_string(),
+        ));
+    }
+    if !tx.price.is_finite() || tx.price < 0.0 {
+        return Err(Error::BADREQUEST("price must not be negative.".to_string()));
+    }
+    if tx.fee.is_some_and(|t| !t.is_finite() || t < 0.0) {
+        return Err(Error::BADREQUEST("fee must not be negative.".to_string()));
+    }
+    if tx.trade_date > Utc::now().date_naive() {
+        return Err(Error::BADREQUEST(
+            "trade_date can not be in the future.".to_string(),
+        ));
+    }
+
+    Ok(kind)
+}
+
+/// Transactions from a CSV export with a header row naming the columns `date`, `type`,
+/// `symbol`, `quantity`, `price` and optionally `fee`, in any order.
+pub fn parse_csv(text: &str) -> Result<Vec<NewTransaction>> {
+    let mut lines = text
+        .trim_start_matches('\u{feff}')
+        .lines()
+        .enumerate()
+        .filter(|(_, t)| !t.trim().is_empty());
+
+    let Some((_, header)) = lines.next() else {
+        return Err(Error::BADREQUEST("the file is empty.".to_string()));
+    };
+
+    let header: Vec<String> = split_csv_line(header)
+        .into_iter()
+        .map(|t| t.to_lowercase())
+        .collect();
+    let column = |names: &[&str]| header.iter().position(|t| names.contains(&t.as_str()));
+    let required = |names: &[&str]| {
+        column(names)
+            .ok_or_else(|| Error::BADREQUEST(format!("the header has no `{}` column.", names[0])))
+    };
+
+    let date = required(&["date", "trade_date"])?;
+    let kind = required(&["type", "kind"])?;
+    let symbol = required(&["symbol"])?;
+    let quantity = required(&["quantity", "shares"])?;
+    let price = required(&["price"])?;
+    let fee = column(&["fee", "fees", "commission"]);
+
+    let mut transactions = vec![];
+    for (index, line) in lines {
+        if transactions.len() == MAX_IMPORT_ROWS {
+            return Err(Error::BADREQUEST(format!(
+                "a file can hold at most {MAX_IMPORT_ROWS} transactions."
+            )));
+        }
+
+        let line_error = |e: Error| Error::BADREQUEST(format!("line {}: {e}", index + 1));
+        let fields = split_csv_line(line);
+        let field = |i: usize| fields.get(i).map(String::as_str).unwrap_or("");
+        let number = |i: usize, name: &str| {
+            field(i).replace(',', "").parse::<f64>().map_err(|_| {
+                line_error(Error::BADREQUEST(format!(
+                    "{name} `{}` is not a number.",
+                    field(i)
+                )))
+            })
+        };
+
+        let tx = NewTransaction {
+            symbol: field(symbol).to_uppercase(),
+            kind: field(kind).to_lowercase(),
+            trade_date: NaiveDate::parse_from_str(field(date), "%Y-%m-%d").map_err(|_| {
+                line_error(Error::BADREQUEST(format!(
+                    "date `{}` is not YYYY-MM-DD.",
+                    field(date)
+                )))
+            })?,
+            quantity: number(quantity, "quantity")?,
+            price: number(price, "price")?,
+            fee: match fee {
+                Some(i) if !field(i).is_empty() => Some(number(i, "fee")?),
+                _ => None,
+            },
+        };
+        check(&tx).map_err(line_error)?;
+
+        transactions.push(tx);
+    }
+
+    Ok(transactions)
+}
+
+/// Fields of one line, unquoting `"..."` and `""` the way spreadsheets write them.
+fn split_csv_line(line: &str) -> Vec<String> {
+    let mut fields = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match (c, quoted) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            ('"', _) => quoted = !quoted,
+            (',', false) => fields.push(std::mem::take(&mut field).trim().to_string()),
+            _ => field.push(c),
+        }
+    }
+    fields.push(field.trim().to_string());
+
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tx(kind: &str, day: u32, quantity: f64, price: f64, fee: f64) -> PortfolioTransaction {
+        PortfolioTransaction {
+            id: day as i32,
+            symbol: "AAPL".to_string(),
+            kind: kind.to_string(),
+            trade_date: NaiveDate::from_ymd_opt(2023, 10, day).unwrap(),
+            quantity,
+            price,
+            fee,
+            created_at: NaiveDate::from_ymd_opt(2023, 10, day)
+                .unwrap()
+                .and_hms_opt(0, 0, 0)
+                .unwrap(),
+        }
+    }
+
+    fn trades() -> Vec<PortfolioTransaction> {
+        vec![
+            tx("buy", 2, 10.0, 100.0, 0.0),
+            tx("buy", 3, 10.0, 120.0, 0.0),
+            tx("sell", 4, 15.0, 130.0, 0.0),
+            tx("dividend", 5, 5.0, 0.24, 0.0),
+        ]
+    }
+
+    #[test]
+    fn fifo_sells_oldest_lots_first() {
+        let position = replay(CostMethod::Fifo, &trades()).unwrap().positions()[0].clone();
+
+        // 10 @ 100 and 5 @ 120 sold for 15 @ 130
+        assert_eq!(position.quantity, 5.0);
+        assert_eq!(position.cost_basis, 600.0);
+        assert_eq!(position.realized, 350.0);
+        assert!((position.dividends - 1.2).abs() < 1e-9);
+    }
+
+    #[test]
+    fn average_cost_spreads_the_basis() {
+        let position = replay(CostMethod::Average, &trades()).unwrap().positions()[0].clone();
+
+        // 20 shares at 110 on average
+        assert_eq!(position.quantity, 5.0);
+        assert_eq!(position.cost_basis, 550.0);
+        assert_eq!(position.realized, 300.0);
+    }
+
+    #[test]
+    fn fees_go_into_cost_and_proceeds() {
+        let book = replay(
+            CostMethod::Fifo,
+            &[
+                tx("buy", 2, 10.0, 100.0, 5.0),
+                tx("sell", 3, 10.0, 110.0, 5.0),
+            ],
+        )
+        .unwrap();
+        let position = &book.positions()[0];
+
+        assert_eq!(position.quantity, 0.0);
+        assert_eq!(position.cost_basis, 0.0);
+        assert_eq!(position.realized, 90.0);
+    }
+
+    #[test]
+    fn overselling_is_rejected() {
+        let err = replay(
+            CostMethod::Fifo,
+            &[
+                tx("buy", 2, 10.0, 100.0, 0.0),
+                tx("sell", 3, 11.0, 100.0, 0.0),
+            ],
+        );
+        assert!(matches!(err, Err(Error::BADREQUEST(_))));
+    }
+
+    #[test]
+    fn history_carries_closes_forward() {
+        let day = |d| NaiveDate::from_ymd_opt(2023, 10, d).unwrap();
+        let closes = HashMap::from([(
+            "AAPL".to_string(),
+            vec![(day(1), 90.0), (day(3), 125.0), (day(5), 140.0)],
+        )]);
+
+        let values = history(
+            CostMethod::Fifo,
+            &trades(),
+            &[day(2), day(3), day(4), day(5)],
+            &closes,
+        )
+        .unwrap();
+        let values: Vec<(f64, f64)> = values
+            .iter()
+            .map(|t| (t.market_value, t.cost_basis))
+            .collect();
+
+        assert_eq!(
+            values,
+            vec![
+                (900.0, 1000.0),
+                (2500.0, 2200.0),
+                (625.0, 600.0),
+                (700.0, 600.0)
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_csv_exports() {
+        let csv = "\u{feff}Date,Symbol,Type,Quantity,Price,Fee\n\
+            2023-10-02,aapl,Buy,\"1,000\",171.5,1\n\
+            \n\
+            2023-10-05,AAPL,dividend,1000,0.24,\n";
+        let transactions = parse_csv(csv).unwrap();
+
+        assert_eq!(transactions.len(), 2);
+        assert_eq!(transactions[0].symbol, "AAPL");
+        assert_eq!(transactions[0].kind, "buy");
+        assert_eq!(transactions[0].quantity, 1000.0);
+        assert_eq!(transactions[0].fee, Some(1.0));
+        assert_eq!(transactions[1].fee, None);
+    }
+
+    #[test]
+    fn csv_errors_name_the_line() {
+        let csv = "date,type,symbol,quantity,price\n2023-10-02,buy,AAPL,ten,171.5\n";
+        let Err(Error::BADREQUEST(message)) = parse_csv(csv) else {
+            panic!("expected a bad request");
+        };
+        assert_eq!(message, "line 2: quantity `ten` is not a number.");
+
+        assert!(parse_csv("date,type,symbol,price\n").is_err());
+    }
+}
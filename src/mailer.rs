@@ -0,0 +1,132 @@
+use crate::error::{Error, Result};
+use crate::template::Lang;
+use lettre::{
+    address::Envelope,
+    message::Mailbox,
+    transport::{
+        file::AsyncFileTransport, sendmail::AsyncSendmailTransport,
+        smtp::authentication::Credentials, stub::AsyncStubTransport,
+    },
+    AsyncSmtpTransport, AsyncTransport, Tokio1Executor,
+};
+use std::env;
+use std::sync::Arc;
+
+/// Outbound transport selected by `MAIL_TRANSPORT` (`smtp`, `sendmail`, `file` or `stub`).
+#[derive(Debug)]
+pub enum Transport {
+    Smtp(AsyncSmtpTransport<Tokio1Executor>),
+    Sendmail(AsyncSendmailTransport<Tokio1Executor>),
+    File(AsyncFileTransport<Tokio1Executor>),
+    Stub(AsyncStubTransport),
+}
+
+#[derive(Clone, Debug)]
+pub struct Mailer {
+    transport: Arc<Transport>,
+    pub sender: Mailbox,
+    pub recipient: Mailbox,
+    pub recipient_lang: Lang,
+    // where links in system mail point, the api is served under `/api`
+    pub public_url: String,
+}
+
+impl Mailer {
+    pub fn new(
+        transport: Transport,
+        sender: Mailbox,
+        recipient: Mailbox,
+        recipient_lang: Lang,
+        public_url: String,
+    ) -> Self {
+        Self {
+            transport: Arc::new(transport),
+            sender,
+            recipient,
+            recipient_lang,
+            public_url,
+        }
+    }
+
+    pub fn from_env() -> Result<Self> {
+        let recipient = env::var("MAIL_RECIPIENT")?
+            .parse::<Mailbox>()
+            .map_err(|e| Error::CUSTOMERROR(format!("MAIL_RECIPIENT is invalid: {e}")))?;
+
+        // system mail (digests, alerts) goes out as MAIL_FROM, or as the owner if unset
+        let sender = match env::var("MAIL_FROM") {
+            Ok(from) => from
+                .parse::<Mailbox>()
+                .map_err(|e| Error::CUSTOMERROR(format!("MAIL_FROM is invalid: {e}")))?,
+            Err(_) => recipient.clone(),
+        };
+
+        let recipient_lang = env::var("MAIL_RECIPIENT_LANG")
+            .map(|t| Lang::from_tag(&t))
+            .unwrap_or_default();
+
+        let public_url = env::var("PUBLIC_URL")
+            .map(|t| t.trim_end_matches('/').to_string())
+            .unwrap_or_else(|_| "http://localhost:3000".to_string());
+
+        let kind = env::var("MAIL_TRANSPORT").unwrap_or_else(|_| "smtp".to_string());
+
+        let transport = match kind.as_str() {
+            "smtp" => {
+                let relay = env::var("SMTP_RELAY")?;
+                let creds =
+                    Credentials::new(env::var("SMTP_USERNAME")?, env::var("SMTP_PASSWORD")?);
+
+                let smtp = AsyncSmtpTransport::<Tokio1Executor>::relay(&relay)
+                    .map_err(|e| Error::LettreFail(e.to_string()))?
+                    .credentials(creds)
+                    .build();
+
+                Transport::Smtp(smtp)
+            }
+            "sendmail" => match env::var("SENDMAIL_COMMAND") {
+                Ok(command) => {
+                    Transport::Sendmail(AsyncSendmailTransport::new_with_command(command))
+                }
+                Err(_) => Transport::Sendmail(AsyncSendmailTransport::new()),
+            },
+            "file" => {
+                let dir = env::var("MAIL_FILE_DIR")?;
+                Transport::File(AsyncFileTransport::new(dir))
+            }
+            "stub" => Transport::Stub(AsyncStubTransport::new_ok()),
+            other => {
+                return Err(Error::CUSTOMERROR(format!(
+                    "MAIL_TRANSPORT `{other}` is not one of smtp, sendmail, file, stub."
+                )))
+            }
+        };
+
+        Ok(Self::new(
+            transport,
+            sender,
+            recipient,
+            recipient_lang,
+            public_url,
+        ))
+    }
+
+    pub async fn send_raw(&self, envelope: &Envelope, email: &[u8]) -> Result<()> {
+        let sent = match self.transport.as_ref() {
+            Transport::Smtp(t) => t
+                .send_raw(envelope, email)
+                .await
+                .map(|_| ())
+                .map_err(|e| e.to_string()),
+            Transport::Sendmail(t) => t.send_raw(envelope, email).await.map_err(|e| e.to_string()),
+            Transport::File(t) => t
+                .send_raw(envelope, email)
+                .await
+                .map(|_| ())
+                .map_err(|e| e.to_string()),
+            Transport::Stub(t) => t.send_raw(envelope, email).await.map_err(|e| e.to_string()),
+        };
+
+        sent.map_err(Error::LettreFail)
+    }
+}
@@ -1,6 +1,6 @@
-use crate::routes::{mail, stock};
+use crate::routes::{alert, auth, mail, market, portfolio, stock, user, watchlist};
 use axum::{
-    http::{header, Method},
+    http::{header, HeaderName, Method},
     Router,
 };
 use std::error::Error;
@@ -9,13 +9,26 @@ use tower_cookies::CookieManagerLayer;
 use tower_http::cors::{Any, CorsLayer};
 // use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
+mod alerting;
 mod app_state;
+mod comparison;
+mod digest;
 mod error;
+mod indicator;
+mod ledger;
+mod mailer;
+mod market_calendar;
 mod model;
+mod outbox;
 mod pagination;
+mod rate_limit;
 mod response;
 mod routes;
 mod routine;
+mod screener;
+mod search;
+mod session;
+mod template;
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
     // tracing_subscriber::registry().with(
@@ -29,19 +42,34 @@ async fn main() -> Result<(), Box<dyn Error>> {
     let cors = CorsLayer::new()
         .allow_methods([Method::GET, Method::POST, Method::PUT, Method::DELETE])
         .allow_origin(Any)
-        .allow_headers([header::CONTENT_TYPE]);
+        .allow_headers([header::CONTENT_TYPE])
+        .expose_headers([
+            header::LINK,
+            HeaderName::from_static("x-pagination-count"),
+            HeaderName::from_static("x-pagination-offset"),
+            HeaderName::from_static("x-pagination-limit"),
+            HeaderName::from_static("x-pagination-next-cursor"),
+        ]);
 
     //sqlx::migrate!("./migrations").run(&app.db).await.unwrap();
 
     tracing::debug!("listening on {}", addr);
 
-    // .merge(auth::routes(&mut app))
     let collect_routes = Router::new()
-        .merge(mail::routes())
-        .merge(stock::routes(&mut app));
+        .merge(auth::routes(&mut app))
+        .merge(mail::routes(&mut app))
+        .merge(market::routes(&mut app))
+        .merge(stock::routes(&mut app))
+        .merge(user::routes(&mut app))
+        .merge(watchlist::routes(&mut app))
+        .merge(portfolio::routes(&mut app))
+        .merge(alert::routes(&mut app));
 
     let _ = routine::routine(app.clone()).await?;
 
+    outbox::spawn_workers(app.clone());
+    alerting::spawn_webhook_worker(app.clone());
+
     let routes = Router::new()
         .nest("/api", collect_routes)
         .layer(cors)
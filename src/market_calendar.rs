@@ -0,0 +1,298 @@
+use crate::error::Result;
+use chrono::{
+    DateTime, Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc, Weekday,
+};
+use chrono_tz::{America::New_York, Tz};
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+pub const PRE_MARKET_OPEN: NaiveTime = match NaiveTime::from_hms_opt(4, 0, 0) {
+    Some(t) => t,
+    None => unreachable!(),
+};
+pub const OPEN: NaiveTime = match NaiveTime::from_hms_opt(9, 30, 0) {
+    Some(t) => t,
+    None => unreachable!(),
+};
+pub const CLOSE: NaiveTime = match NaiveTime::from_hms_opt(16, 0, 0) {
+    Some(t) => t,
+    None => unreachable!(),
+};
+
+// extended hours end four hours after the close, 20:00 normally and 17:00 on half days
+const AFTER_HOURS_SPAN: i64 = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MarketPhase {
+    PreMarket,
+    Open,
+    AfterHours,
+    Closed,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct MarketHoliday {
+    pub holiday_date: NaiveDate,
+    pub holiday_name: String,
+    pub early_close: Option<NaiveTime>,
+}
+
+/// One NYSE/NASDAQ regular session, in `America/New_York`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Session {
+    pub date: NaiveDate,
+    pub open: DateTime<Tz>,
+    pub close: DateTime<Tz>,
+}
+
+impl Session {
+    /// Open as stored in `demo_app.stock_price.time`, which holds New York wall-clock time.
+    pub fn open_local(&self) -> NaiveDateTime {
+        self.open.naive_local()
+    }
+}
+
+/// Regular trading hours for NYSE/NASDAQ, with holidays and half days from
+/// `demo_app.market_holiday`.
+#[derive(Debug, Clone, Default)]
+pub struct MarketCalendar {
+    // `None` closes the whole day, `Some(t)` closes early at `t`
+    holidays: Arc<HashMap<NaiveDate, Option<NaiveTime>>>,
+}
+
+impl MarketCalendar {
+    pub fn new(holidays: impl IntoIterator<Item = MarketHoliday>) -> Self {
+        let holidays = holidays
+            .into_iter()
+            .map(|t| (t.holiday_date, t.early_close))
+            .collect();
+
+        Self {
+            holidays: Arc::new(holidays),
+        }
+    }
+
+    pub async fn load(db: &PgPool) -> Result<Self> {
+        let holidays = sqlx::query_as::<_, MarketHoliday>(
+            "SELECT holiday_date, holiday_name, early_close FROM demo_app.market_holiday",
+        )
+        .fetch_all(db)
+        .await?;
+
+        Ok(Self::new(holidays))
+    }
+
+    pub fn now() -> DateTime<Tz> {
+        Utc::now().with_timezone(&New_York)
+    }
+
+    pub fn is_trading_day(&self, date: NaiveDate) -> bool {
+        !matches!(date.weekday(), Weekday::Sat | Weekday::Sun)
+            && !matches!(self.holidays.get(&date), Some(None))
+    }
+
+    pub fn session(&self, date: NaiveDate) -> Option<Session> {
+        if !self.is_trading_day(date) {
+            return None;
+        }
+
+        let close = self.holidays.get(&date).copied().flatten().unwrap_or(CLOSE);
+
+        Some(Session {
+            date,
+            open: new_york(date.and_time(OPEN)),
+            close: new_york(date.and_time(close)),
+        })
+    }
+
+    pub fn previous_trading_day(&self, date: NaiveDate) -> NaiveDate {
+        let mut day = date - Duration::days(1);
+        while !self.is_trading_day(day) {
+            day -= Duration::days(1);
+        }
+        day
+    }
+
+    pub fn next_trading_day(&self, date: NaiveDate) -> NaiveDate {
+        let mut day = date + Duration::days(1);
+        while !self.is_trading_day(day) {
+            day += Duration::days(1);
+        }
+        day
+    }
+
+    /// The latest session that had closed by `at`.
+    pub fn last_session(&self, at: DateTime<Tz>) -> Session {
+        let today = at.date_naive();
+        match self.session(today) {
+            Some(session) if session.close <= at => session,
+            _ => self.must_session(self.previous_trading_day(today)),
+        }
+    }
+
+    /// The session in progress at `at`, if any.
+    pub fn current_session(&self, at: DateTime<Tz>) -> Option<Session> {
+        self.session(at.date_naive())
+            .filter(|t| t.open <= at && at < t.close)
+    }
+
+    pub fn is_open(&self, at: DateTime<Tz>) -> bool {
+        self.current_session(at).is_some()
+    }
+
+    pub fn phase(&self, at: DateTime<Tz>) -> MarketPhase {
+        let Some(session) = self.session(at.date_naive()) else {
+            return MarketPhase::Closed;
+        };
+
+        let pre_market = new_york(session.date.and_time(PRE_MARKET_OPEN));
+        let after_hours = session.close + Duration::hours(AFTER_HOURS_SPAN);
+
+        if at < pre_market {
+            MarketPhase::Closed
+        } else if at < session.open {
+            MarketPhase::PreMarket
+        } else if at < session.close {
+            MarketPhase::Open
+        } else if at < after_hours {
+            MarketPhase::AfterHours
+        } else {
+            MarketPhase::Closed
+        }
+    }
+
+    /// The first session opening strictly after `at`.
+    pub fn next_open(&self, at: DateTime<Tz>) -> Session {
+        let today = at.date_naive();
+        match self.session(today) {
+            Some(session) if at < session.open => session,
+            _ => self.must_session(self.next_trading_day(today)),
+        }
+    }
+
+    fn must_session(&self, date: NaiveDate) -> Session {
+        self.session(date)
+            .expect("previous/next_trading_day only returns trading days")
+    }
+}
+
+fn new_york(local: NaiveDateTime) -> DateTime<Tz> {
+    // DST switches at 02:00, never during trading hours
+    New_York
+        .from_local_datetime(&local)
+        .single()
+        .expect("market hours are never inside a DST transition")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn calendar() -> MarketCalendar {
+        let holiday = |date: &str, early_close: Option<NaiveTime>| MarketHoliday {
+            holiday_date: date.parse().unwrap(),
+            holiday_name: String::new(),
+            early_close,
+        };
+
+        MarketCalendar::new([
+            holiday("2023-11-23", None),
+            holiday("2023-11-24", NaiveTime::from_hms_opt(13, 0, 0)),
+            holiday("2024-01-15", None),
+        ])
+    }
+
+    fn ny(local: &str) -> DateTime<Tz> {
+        new_york(NaiveDateTime::parse_from_str(local, "%Y-%m-%d %H:%M").unwrap())
+    }
+
+    fn date(t: &str) -> NaiveDate {
+        t.parse().unwrap()
+    }
+
+    #[test]
+    fn skips_weekends_and_holidays() {
+        let cal = calendar();
+        assert!(!cal.is_trading_day(date("2023-11-23")));
+        assert!(cal.is_trading_day(date("2023-11-24")));
+        assert_eq!(
+            cal.previous_trading_day(date("2024-01-16")),
+            date("2024-01-12")
+        );
+        assert_eq!(cal.next_trading_day(date("2023-11-22")), date("2023-11-24"));
+    }
+
+    #[test]
+    fn last_session_waits_for_the_close() {
+        let cal = calendar();
+        assert_eq!(
+            cal.last_session(ny("2023-11-22 15:59")).date,
+            date("2023-11-21")
+        );
+        assert_eq!(
+            cal.last_session(ny("2023-11-22 16:00")).date,
+            date("2023-11-22")
+        );
+        assert_eq!(
+            cal.last_session(ny("2023-11-26 10:00")).date,
+            date("2023-11-24")
+        );
+    }
+
+    #[test]
+    fn early_close() {
+        let cal = calendar();
+        let session = cal.session(date("2023-11-24")).unwrap();
+        assert_eq!(session.close, ny("2023-11-24 13:00"));
+        assert!(!cal.is_open(ny("2023-11-24 13:30")));
+        assert_eq!(
+            cal.last_session(ny("2023-11-24 13:30")).date,
+            date("2023-11-24")
+        );
+    }
+
+    #[test]
+    fn open_and_next_open() {
+        let cal = calendar();
+        assert!(cal.is_open(ny("2024-01-12 09:30")));
+        assert!(!cal.is_open(ny("2024-01-12 16:00")));
+        assert_eq!(
+            cal.next_open(ny("2024-01-12 09:30")).date,
+            date("2024-01-16")
+        );
+        assert_eq!(
+            cal.next_open(ny("2024-01-16 08:00")).date,
+            date("2024-01-16")
+        );
+    }
+
+    #[test]
+    fn phases() {
+        let cal = calendar();
+        assert_eq!(cal.phase(ny("2024-01-12 03:59")), MarketPhase::Closed);
+        assert_eq!(cal.phase(ny("2024-01-12 04:00")), MarketPhase::PreMarket);
+        assert_eq!(cal.phase(ny("2024-01-12 12:00")), MarketPhase::Open);
+        assert_eq!(cal.phase(ny("2024-01-12 19:59")), MarketPhase::AfterHours);
+        assert_eq!(cal.phase(ny("2024-01-12 20:00")), MarketPhase::Closed);
+        assert_eq!(cal.phase(ny("2023-11-24 16:59")), MarketPhase::AfterHours);
+        assert_eq!(cal.phase(ny("2023-11-24 17:00")), MarketPhase::Closed);
+        assert_eq!(cal.phase(ny("2024-01-15 12:00")), MarketPhase::Closed);
+    }
+
+    #[test]
+    fn follows_new_york_dst() {
+        let cal = calendar();
+        let summer = cal.session(date("2023-07-05")).unwrap();
+        let winter = cal.session(date("2023-12-05")).unwrap();
+        assert_eq!(
+            summer.open.with_timezone(&Utc).format("%H:%M").to_string(),
+            "13:30"
+        );
+        assert_eq!(
+            winter.open.with_timezone(&Utc).format("%H:%M").to_string(),
+            "14:30"
+        );
+    }
+}
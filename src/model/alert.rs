@@ -0,0 +1,113 @@
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::types::Json;
+use sqlx::FromRow;
+use uuid::Uuid;
+use validator::Validate;
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct Alert {
+    pub id: i32,
+    pub symbol: String,
+    pub kind: String,
+    pub direction: String,
+    pub threshold: Option<f64>,
+    pub cooldown_minutes: i32,
+    pub notify_email: bool,
+    pub webhook_url: Option<String>,
+    pub active: bool,
+    pub last_triggered_at: Option<DateTime<Utc>>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+/// The HMAC key for the webhook signature is only shown when it is made, on create and
+/// when it is rotated.
+#[derive(Debug, Serialize)]
+pub struct CreatedAlert {
+    #[serde(flatten)]
+    pub alert: Alert,
+    pub webhook_secret: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WebhookSecret {
+    pub webhook_secret: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct SaveAlert {
+    pub symbol: String,
+    pub kind: String,
+    pub direction: Option<String>,
+    pub threshold: Option<f64>,
+    #[validate(range(min = 0, max = 43200, message = "must be 0 to 43200 minutes"))]
+    pub cooldown_minutes: Option<i32>,
+    pub notify_email: Option<bool>,
+    #[validate(url(message = "must be a valid url"))]
+    pub webhook_url: Option<String>,
+    pub active: Option<bool>,
+}
+
+#[derive(Debug, FromRow)]
+pub struct ActiveAlert {
+    pub id: i32,
+    pub symbol: String,
+    pub kind: String,
+    pub direction: String,
+    pub threshold: Option<f64>,
+    pub cooldown_minutes: i32,
+    pub notify_email: bool,
+    pub webhook_url: Option<String>,
+    pub last_state: Option<bool>,
+    pub last_evaluated_date: Option<NaiveDate>,
+    pub last_triggered_at: Option<DateTime<Utc>>,
+    pub username: String,
+    pub email: Option<String>,
+    pub language: String,
+}
+
+/// The latest daily bar of a symbol with what the alert conditions look at.
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct BarSnapshot {
+    pub symbol: String,
+    pub date: NaiveDate,
+    pub close: f64,
+    pub prev_close: Option<f64>,
+    pub volume: f64,
+    pub vol_avg: Option<f64>,
+    pub ema_fast: Option<f64>,
+    pub ema_slow: Option<f64>,
+}
+
+/// What the email template and the webhook receive, also kept in the trigger history.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AlertEvent {
+    pub alert_id: i32,
+    pub symbol: String,
+    pub kind: String,
+    pub direction: String,
+    pub threshold: Option<f64>,
+    pub message: String,
+    pub date: NaiveDate,
+    pub close: f64,
+    pub change_percent: Option<f64>,
+    pub volume: f64,
+    pub vol_avg: Option<f64>,
+    pub ema_fast: Option<f64>,
+    pub ema_slow: Option<f64>,
+    pub triggered_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct AlertTrigger {
+    pub id: i32,
+    pub bar_date: NaiveDate,
+    pub payload: Json<AlertEvent>,
+    pub outbox_id: Option<Uuid>,
+    pub email_status: Option<String>,
+    pub webhook_status: Option<String>,
+    pub webhook_attempts: i32,
+    pub webhook_error: Option<String>,
+    pub triggered_at: DateTime<Utc>,
+}
@@ -0,0 +1,88 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+use validator::Validate;
+
+#[derive(Debug, FromRow)]
+pub struct DigestRecipient {
+    pub usr_id: i32,
+    pub username: String,
+    pub email: String,
+    pub language: String,
+}
+
+#[derive(Debug, FromRow)]
+pub struct WatchedSymbol {
+    pub usr_id: i32,
+    pub symbol: String,
+}
+
+#[derive(Debug, FromRow)]
+pub struct DigestQuote {
+    pub symbol: String,
+    pub company_name: String,
+    pub close: f32,
+    pub prev_close: Option<f32>,
+    pub ema: Vec<f32>,
+    pub prev_ema: Option<Vec<f32>>,
+}
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct Headline {
+    pub symbol: String,
+    pub id: Uuid,
+    pub title: String,
+    pub link: String,
+    pub published_date: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CrossDirection {
+    Above,
+    Below,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct EmaCross {
+    pub fast: u32,
+    pub slow: u32,
+    pub direction: CrossDirection,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DigestSymbol {
+    pub symbol: String,
+    pub company_name: String,
+    pub close: f32,
+    pub change: Option<f32>,
+    pub change_percent: Option<f32>,
+    pub ema_crosses: Vec<EmaCross>,
+    pub headlines: Vec<Headline>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct DigestSettings {
+    pub digest_opt_in: bool,
+    pub email: Option<String>,
+    pub language: String,
+    // waiting on its confirmation link, replaces `email` once followed
+    pub pending_email: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct UpdateDigestSettings {
+    pub digest_opt_in: bool,
+    #[validate(email(message = "must be a valid email address"))]
+    pub email: Option<String>,
+    pub language: Option<String>,
+}
+
+#[derive(Debug, FromRow)]
+pub struct EmailConfirmation {
+    pub usr_id: i32,
+    pub email: String,
+    pub digest_opt_in: bool,
+    pub expired: bool,
+}
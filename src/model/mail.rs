@@ -1,8 +1,46 @@
+use chrono::{DateTime, NaiveDateTime, Utc};
 use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
 use validator::Validate;
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Validate)]
 pub struct Mail {
+    #[validate(email(message = "must be a valid email address"))]
     pub from: String,
+    #[validate(length(min = 1, max = 200, message = "must be 1 to 200 characters"))]
     pub subject: String,
+    #[validate(length(min = 10, max = 5000, message = "must be 10 to 5000 characters"))]
     pub body: String,
+    // honeypot, hidden by the frontend so only bots fill it in
+    #[serde(default)]
+    pub website: Option<String>,
+    // from `GET /mail/token` when the form was rendered, good for one submit
+    pub form_token: Uuid,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FormToken {
+    pub form_token: Uuid,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct ContactMessage {
+    pub id: Uuid,
+    pub sender: String,
+    pub subject: String,
+    pub body: String,
+    pub ip_address: Option<String>,
+    pub delivery_status: String,
+    pub delivery_error: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct QueueStats {
+    pub pending: i64,
+    pub sending: i64,
+    pub sent: i64,
+    pub dead: i64,
+    pub oldest_pending: Option<DateTime<Utc>>,
+    pub last_dead_error: Option<String>,
 }
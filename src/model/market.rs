@@ -0,0 +1,65 @@
+use crate::market_calendar::MarketPhase;
+use crate::model::stock::StockPriceEachSector;
+use chrono::{DateTime, FixedOffset, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+#[derive(Debug, Serialize)]
+pub struct Timestamp {
+    pub utc: DateTime<Utc>,
+    pub local: DateTime<FixedOffset>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SessionTimes {
+    pub date: NaiveDate,
+    pub open: Timestamp,
+    pub close: Timestamp,
+    pub early_close: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MarketStatus {
+    pub phase: MarketPhase,
+    pub is_open: bool,
+    pub timezone: String,
+    pub exchange_timezone: String,
+    pub now: Timestamp,
+    pub session: Option<SessionTimes>,
+    pub next_session: SessionTimes,
+    pub latest_bar: Option<Timestamp>,
+    pub data_delayed: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FMPIndexQuote {
+    pub price: Option<f64>,
+    pub change: Option<f64>,
+    pub changes_percentage: Option<f64>,
+    pub day_low: Option<f64>,
+    pub day_high: Option<f64>,
+    pub previous_close: Option<f64>,
+    pub timestamp: Option<i64>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct IndexQuote {
+    pub symbol: String,
+    pub name: Option<String>,
+    pub price: Option<f64>,
+    pub change: Option<f64>,
+    pub change_percent: Option<f64>,
+    pub day_low: Option<f64>,
+    pub day_high: Option<f64>,
+    pub previous_close: Option<f64>,
+    pub quoted_at: Option<DateTime<Utc>>,
+    // 15 minute closes of the last session, oldest first
+    pub sparkline: Vec<f32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MarketOverview {
+    pub indices: Vec<IndexQuote>,
+    pub sectors: Vec<StockPriceEachSector>,
+}
@@ -1,4 +1,10 @@
+pub mod alert;
 pub mod commodities;
+pub mod digest;
 pub mod mail;
+pub mod market;
+pub mod portfolio;
+pub mod screener;
 pub mod stock;
 pub mod user;
+pub mod watchlist;
@@ -0,0 +1,103 @@
+use chrono::{NaiveDate, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use validator::Validate;
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct Portfolio {
+    pub id: i32,
+    pub name: String,
+    pub cost_method: String,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct SavePortfolio {
+    #[validate(length(min = 1, max = 64, message = "must be 1 to 64 characters"))]
+    pub name: String,
+    // fifo when left out on create, unchanged when left out on update
+    pub cost_method: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct PortfolioTransaction {
+    pub id: i32,
+    pub symbol: String,
+    pub kind: String,
+    pub trade_date: NaiveDate,
+    pub quantity: f64,
+    pub price: f64,
+    pub fee: f64,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewTransaction {
+    pub symbol: String,
+    pub kind: String,
+    pub trade_date: NaiveDate,
+    pub quantity: f64,
+    pub price: f64,
+    pub fee: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImportResult {
+    pub imported: usize,
+}
+
+#[derive(Debug, FromRow)]
+pub struct Valuation {
+    pub symbol: String,
+    pub company_name: Option<String>,
+    pub sector: Option<String>,
+    pub price: Option<f32>,
+    pub priced_at: Option<NaiveDateTime>,
+    pub from_close: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Holding {
+    pub symbol: String,
+    pub company_name: Option<String>,
+    pub sector: Option<String>,
+    pub quantity: f64,
+    pub average_cost: Option<f64>,
+    pub cost_basis: f64,
+    pub price: Option<f64>,
+    // "close" for the latest stock_price row, "snapshot" for stock_profile.price
+    pub price_source: Option<&'static str>,
+    pub priced_at: Option<NaiveDateTime>,
+    pub market_value: Option<f64>,
+    pub unrealized: Option<f64>,
+    pub unrealized_percent: Option<f64>,
+    pub realized: f64,
+    pub dividends: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PortfolioSummary {
+    #[serde(flatten)]
+    pub portfolio: Portfolio,
+    pub market_value: f64,
+    pub cost_basis: f64,
+    pub unrealized: f64,
+    pub realized: f64,
+    pub dividends: f64,
+    pub holdings: Vec<Holding>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PortfolioValue {
+    pub date: NaiveDate,
+    pub market_value: f64,
+    pub cost_basis: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SectorAllocation {
+    pub sector: Option<String>,
+    pub market_value: f64,
+    pub weight: f64,
+}
@@ -0,0 +1,53 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::FromRow;
+
+/// A screen is a tree of `and` / `or` / `not` nodes over field rules, e.g.
+/// `{"and": [{"field": "sector", "op": "eq", "value": "Technology"}, {"not": {...}}]}`.
+#[derive(Debug, Deserialize)]
+#[serde(untagged, deny_unknown_fields)]
+pub enum Filter {
+    And { and: Vec<Filter> },
+    Or { or: Vec<Filter> },
+    Not { not: Box<Filter> },
+    Rule(Rule),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    pub field: String,
+    pub op: String,
+    pub value: Value,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ScreenRequest {
+    pub filter: Option<Filter>,
+    pub sort: Option<String>,
+    pub order: Option<String>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct ScreenRow {
+    pub symbol: String,
+    pub company_name: Option<String>,
+    pub img: Option<String>,
+    pub sector: Option<String>,
+    pub industry: Option<String>,
+    pub country: Option<String>,
+    pub exchange: Option<String>,
+    pub price: Option<f64>,
+    pub mkt_cap: Option<f64>,
+    pub beta: Option<f64>,
+    pub last_div: Option<f64>,
+    pub vol_avg: Option<f64>,
+    pub dcf: Option<f64>,
+    pub dcf_upside: Option<f64>,
+    pub is_etf: bool,
+    pub is_fund: bool,
+    pub is_adr: bool,
+    pub change_percent: Option<f64>,
+    pub rsi: Option<f64>,
+    pub from_52w_high: Option<f64>,
+}
@@ -1,4 +1,5 @@
 use crate::error::Error;
+use crate::indicator::{IndicatorKind, IndicatorValue};
 use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
@@ -145,9 +146,41 @@ pub struct Symbols {
     pub img: String,
 }
 
-#[derive(Debug, Serialize, Deserialize, FromRow)]
-pub struct SymbolLists {
-    pub symbol_list: Value,
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MatchHighlight {
+    pub field: String,
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SymbolMatch {
+    pub symbol: String,
+    pub company_name: String,
+    pub img: Option<String>,
+    pub isin: Option<String>,
+    pub cusip: Option<String>,
+    pub cik: Option<String>,
+    pub tier: i32,
+    pub score: f32,
+    pub highlights: Vec<MatchHighlight>,
+}
+
+impl<'r> FromRow<'r, PgRow> for SymbolMatch {
+    fn from_row(row: &'r PgRow) -> Result<Self, SqlxError> {
+        Ok(SymbolMatch {
+            symbol: row.try_get("symbol")?,
+            company_name: row.try_get("company_name")?,
+            img: row.try_get("img")?,
+            isin: row.try_get("isin")?,
+            cusip: row.try_get("cusip")?,
+            cik: row.try_get("cik")?,
+            tier: row.try_get("tier")?,
+            score: row.try_get("score")?,
+            // filled in from the query text after the fetch
+            highlights: vec![],
+        })
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -188,67 +221,148 @@ pub struct HistroicalStockPrice {
     pub historical: Vec<Histroical>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct EmaIndicator {
-    #[serde(flatten)]
-    pub daily: DailyPrice,
-    pub ema: f32,
+#[derive(Debug, Serialize, FromRow)]
+pub struct DailyBar {
+    pub symbol: String,
+    pub date: NaiveDate,
+    pub open: f32,
+    pub high: f32,
+    pub low: f32,
+    pub close: f32,
+    pub volume: f32,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct SmaIndicator {
-    #[serde(flatten)]
-    pub daily: DailyPrice,
-    pub sma: f32,
+#[derive(Debug, Serialize, FromRow)]
+pub struct DailyPriceView {
+    pub time: NaiveDateTime,
+    pub open: f32,
+    pub close: f32,
+    pub high: f32,
+    pub low: f32,
+    pub volume: f32,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct StockPrice {
-    bucket: NaiveDateTime,
-    symbol: String,
-    open: f32,
-    close: f32,
-    high: f32,
-    low: f32,
-    ema: [f32; 3],
-    sma: [f32; 3],
-    volume: f32,
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum CandleInterval {
+    #[serde(rename = "5m")]
+    FiveMinutes,
+    #[serde(rename = "15m")]
+    FifteenMinutes,
+    #[serde(rename = "1h")]
+    Hour,
+    #[serde(rename = "1d")]
+    Day,
+    #[serde(rename = "1w")]
+    Week,
+    #[serde(rename = "1M")]
+    Month,
 }
 
-impl<'r> FromRow<'r, PgRow> for StockPrice {
-    fn from_row(row: &'r PgRow) -> Result<Self, SqlxError> {
-        let bucket = row.try_get("bucket")?;
-        let symbol = row.try_get("symbol")?;
-        let open = row.try_get("open")?;
-        let close = row.try_get("close")?;
-        let high = row.try_get("high")?;
-        let low = row.try_get("low")?;
-        let ema = row.try_get("ema")?;
-        let sma = row.try_get("sma")?;
-        let volume = row.try_get("volume")?;
-
-        Ok(StockPrice {
-            bucket,
-            symbol,
-            open,
-            close,
-            high,
-            low,
-            ema,
-            sma,
-            volume,
-        })
+impl CandleInterval {
+    pub const ALL: [&'static str; 6] = ["5m", "15m", "1h", "1d", "1w", "1M"];
+
+    /// Bucket width handed to `time_bucket`.
+    pub fn bucket(&self) -> &'static str {
+        match self {
+            CandleInterval::FiveMinutes => "5 minutes",
+            CandleInterval::FifteenMinutes => "15 minutes",
+            CandleInterval::Hour => "1 hour",
+            CandleInterval::Day => "1 day",
+            CandleInterval::Week => "1 week",
+            CandleInterval::Month => "1 month",
+        }
+    }
+
+    /// Intraday candles are built from the minute rows, the rest from the daily rows.
+    pub fn is_intraday(&self) -> bool {
+        matches!(
+            self,
+            CandleInterval::FiveMinutes | CandleInterval::FifteenMinutes | CandleInterval::Hour
+        )
+    }
+
+    /// Buckets in a year of regular sessions, used to annualize volatility.
+    pub fn periods_per_year(&self) -> f64 {
+        match self {
+            CandleInterval::FiveMinutes => 78.0 * 252.0,
+            CandleInterval::FifteenMinutes => 26.0 * 252.0,
+            // the last bucket of a 6.5 hour session holds half an hour
+            CandleInterval::Hour => 7.0 * 252.0,
+            CandleInterval::Day => 252.0,
+            CandleInterval::Week => 52.0,
+            CandleInterval::Month => 12.0,
+        }
+    }
+}
+
+impl std::str::FromStr for CandleInterval {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "5m" => Ok(CandleInterval::FiveMinutes),
+            "15m" => Ok(CandleInterval::FifteenMinutes),
+            "1h" => Ok(CandleInterval::Hour),
+            "1d" => Ok(CandleInterval::Day),
+            "1w" => Ok(CandleInterval::Week),
+            "1M" => Ok(CandleInterval::Month),
+            _ => Err(Error::BADREQUEST(format!(
+                "interval must be one of {}.",
+                CandleInterval::ALL.join(", ")
+            ))),
+        }
     }
 }
 
 #[derive(Debug, Serialize, FromRow)]
-pub struct DailyPriceView {
-    time: NaiveDateTime,
-    open: f32,
-    close: f32,
-    high: f32,
-    low: f32,
-    volume: f32,
+pub struct Candle {
+    pub time: NaiveDateTime,
+    pub open: f32,
+    pub high: f32,
+    pub low: f32,
+    pub close: f32,
+    pub volume: f32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Candles {
+    pub symbol: String,
+    pub interval: CandleInterval,
+    pub candles: Vec<Candle>,
+}
+
+/// Closes of several symbols on one time axis, `rebased` is the percent change since the
+/// first time every symbol has a bar.
+#[derive(Debug, Serialize)]
+pub struct Comparison {
+    pub interval: CandleInterval,
+    pub times: Vec<NaiveDateTime>,
+    pub series: Vec<ComparedSeries>,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct ComparedSeries {
+    pub symbol: String,
+    pub closes: Vec<f64>,
+    pub rebased: Vec<f64>,
+    pub total_return: f64,
+    pub volatility: Option<f64>,
+    pub max_drawdown: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct IndicatorPoint {
+    pub date: NaiveDate,
+    pub value: IndicatorValue,
+}
+
+#[derive(Debug, Serialize)]
+pub struct IndicatorSeries {
+    pub symbol: String,
+    #[serde(rename = "type")]
+    pub kind: IndicatorKind,
+    pub period: usize,
+    pub values: Vec<IndicatorPoint>,
 }
 
 #[derive(Debug, Serialize, FromRow)]
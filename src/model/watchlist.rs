@@ -0,0 +1,79 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+use validator::Validate;
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct Watchlist {
+    pub id: i32,
+    pub name: String,
+    pub share_token: Option<Uuid>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct WatchlistSummary {
+    pub id: i32,
+    pub name: String,
+    pub symbol_count: i64,
+    pub shared: bool,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct WatchlistMember {
+    pub symbol: String,
+    pub company_name: String,
+    pub position: i32,
+    pub added_at: NaiveDateTime,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WatchlistDetail {
+    #[serde(flatten)]
+    pub watchlist: Watchlist,
+    pub symbols: Vec<WatchlistMember>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct SaveWatchlist {
+    #[validate(length(min = 1, max = 64, message = "must be 1 to 64 characters"))]
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AddSymbol {
+    pub symbol: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReorderSymbols {
+    pub symbols: Vec<String>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct WatchlistQuote {
+    pub symbol: String,
+    pub company_name: String,
+    pub img: Option<String>,
+    pub price: Option<f32>,
+    pub change: Option<f32>,
+    pub change_percent: Option<f32>,
+    // 15 minute closes of the last session, oldest first
+    pub sparkline: Vec<f32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ShareLink {
+    pub token: Uuid,
+    pub path: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SharedWatchlist {
+    pub name: String,
+    pub quotes: Vec<WatchlistQuote>,
+}
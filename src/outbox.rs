@@ -0,0 +1,148 @@
+use crate::app_state::AppState;
+use crate::error::Result;
+use lettre::{address::Envelope, Message};
+use sqlx::{types::Json, PgExecutor};
+use std::env;
+use tokio::time::{sleep, Duration};
+use tracing::error;
+use uuid::Uuid;
+
+const BATCH_SIZE: i64 = 10;
+pub(crate) const POLL_INTERVAL: Duration = Duration::from_secs(5);
+const BACKOFF_BASE_SECS: i64 = 30;
+const BACKOFF_MAX_SECS: i64 = 6 * 60 * 60;
+// a job left in `sending` this long belongs to a worker that died mid-send
+pub(crate) const STALE_LOCK_SECS: i64 = 10 * 60;
+
+#[derive(Debug, Clone, Copy)]
+pub enum MailKind {
+    Contact,
+    Digest,
+    Alert,
+    ConfirmEmail,
+}
+
+impl MailKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MailKind::Contact => "contact",
+            MailKind::Digest => "digest",
+            MailKind::Alert => "alert",
+            MailKind::ConfirmEmail => "confirm_email",
+        }
+    }
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct OutboxJob {
+    id: Uuid,
+    envelope: Json<Envelope>,
+    raw: Vec<u8>,
+    attempts: i32,
+    max_attempts: i32,
+}
+
+/// Queue `message` for delivery. Pass a transaction to enqueue atomically with other writes.
+pub async fn enqueue<'e, E>(executor: E, kind: MailKind, message: &Message) -> Result<Uuid>
+where
+    E: PgExecutor<'e>,
+{
+    let (id,) = sqlx::query_as::<_, (Uuid,)>(
+        "INSERT INTO weblog.mail_outbox(kind, envelope, raw) VALUES ($1, $2, $3) RETURNING id",
+    )
+    .bind(kind.as_str())
+    .bind(Json(message.envelope()))
+    .bind(message.formatted())
+    .fetch_one(executor)
+    .await?;
+
+    Ok(id)
+}
+
+pub fn spawn_workers(app: AppState) {
+    let workers = env::var("MAIL_WORKERS")
+        .ok()
+        .and_then(|t| t.parse::<usize>().ok())
+        .unwrap_or(2);
+
+    for _ in 0..workers {
+        tokio::spawn(worker(app.clone()));
+    }
+}
+
+async fn worker(app: AppState) {
+    loop {
+        match run_batch(&app).await {
+            Ok(0) => sleep(POLL_INTERVAL).await,
+            Ok(_) => {}
+            Err(e) => {
+                error!("mail outbox worker: {e}");
+                sleep(POLL_INTERVAL).await;
+            }
+        }
+    }
+}
+
+async fn run_batch(app: &AppState) -> Result<usize> {
+    let jobs = sqlx::query_as::<_, OutboxJob>(
+        "UPDATE weblog.mail_outbox
+        SET status = 'sending', locked_at = current_timestamp, attempts = attempts + 1
+        WHERE id IN (
+            SELECT id FROM weblog.mail_outbox
+            WHERE (status = 'pending' AND next_attempt_at <= current_timestamp)
+                OR (status = 'sending' AND locked_at < current_timestamp - make_interval(secs => ($1)))
+            ORDER BY next_attempt_at ASC
+            LIMIT ($2)
+            FOR UPDATE SKIP LOCKED
+        )
+        RETURNING id, envelope, raw, attempts, max_attempts",
+    )
+    .bind(STALE_LOCK_SECS as f64)
+    .bind(BATCH_SIZE)
+    .fetch_all(&app.db)
+    .await?;
+
+    let claimed = jobs.len();
+
+    for job in jobs {
+        match app.mailer.send_raw(&job.envelope, &job.raw).await {
+            Ok(_) => {
+                sqlx::query(
+                    "UPDATE weblog.mail_outbox
+                    SET status = 'sent', sent_at = current_timestamp, locked_at = NULL, last_error = NULL
+                    WHERE id = ($1)",
+                )
+                .bind(job.id)
+                .execute(&app.db)
+                .await?;
+            }
+            Err(e) => {
+                let status = if job.attempts >= job.max_attempts {
+                    "dead"
+                } else {
+                    "pending"
+                };
+
+                sqlx::query(
+                    "UPDATE weblog.mail_outbox
+                    SET status = ($1), last_error = ($2), locked_at = NULL,
+                        next_attempt_at = current_timestamp + make_interval(secs => ($3))
+                    WHERE id = ($4)",
+                )
+                .bind(status)
+                .bind(e.to_string())
+                .bind(backoff_secs(job.attempts) as f64)
+                .bind(job.id)
+                .execute(&app.db)
+                .await?;
+            }
+        }
+    }
+
+    Ok(claimed)
+}
+
+pub(crate) fn backoff_secs(attempts: i32) -> i64 {
+    let exp = (attempts - 1).clamp(0, 20) as u32;
+    (BACKOFF_BASE_SECS * 2_i64.pow(exp)).min(BACKOFF_MAX_SECS)
+}
@@ -1,4 +1,9 @@
+use crate::error::{Error, Result};
+use axum::http::Uri;
+use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+use uuid::Uuid;
 
 const LIMIT: u64 = 100;
 const OFFSET: u64 = 0;
@@ -8,6 +13,8 @@ pub struct Pagination {
     pub count: usize,
     pub limit: u64,
     pub offset: u64,
+    pub next_cursor: Option<String>,
+    pub link: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -15,19 +22,94 @@ pub struct RequestQuery {
     pub from: Option<String>,
     pub limit: Option<u64>,
     pub offset: Option<u64>,
+    pub cursor: Option<String>,
+}
+
+/// Position in a time ordered feed, handed to clients as an opaque string.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cursor {
+    pub time: NaiveDateTime,
+    pub id: Option<Uuid>,
+}
+
+impl Cursor {
+    pub fn new(time: NaiveDateTime, id: Option<Uuid>) -> Self {
+        Self { time, id }
+    }
+
+    pub fn from_query(query: &RequestQuery) -> Result<Option<Self>> {
+        query.cursor.as_deref().map(Cursor::decode).transpose()
+    }
+
+    pub fn encode(&self) -> String {
+        let mut raw = self.time.timestamp_micros().to_string();
+        if let Some(id) = self.id {
+            raw = format!("{raw}.{}", id.simple());
+        }
+
+        raw.bytes().fold(String::new(), |mut out, b| {
+            let _ = write!(out, "{b:02x}");
+            out
+        })
+    }
+
+    pub fn decode(cursor: &str) -> Result<Self> {
+        let invalid = || Error::BADREQUEST("cursor is invalid.".to_string());
+
+        let bytes = cursor
+            .as_bytes()
+            .chunks(2)
+            .map(|pair| {
+                std::str::from_utf8(pair)
+                    .ok()
+                    .filter(|t| t.len() == 2)
+                    .and_then(|t| u8::from_str_radix(t, 16).ok())
+            })
+            .collect::<Option<Vec<u8>>>()
+            .ok_or_else(invalid)?;
+        let raw = String::from_utf8(bytes).map_err(|_| invalid())?;
+
+        let (micros, id) = match raw.split_once('.') {
+            Some((micros, id)) => (micros, Some(Uuid::parse_str(id).map_err(|_| invalid())?)),
+            None => (raw.as_str(), None),
+        };
+
+        let time = micros
+            .parse::<i64>()
+            .ok()
+            .and_then(NaiveDateTime::from_timestamp_micros)
+            .ok_or_else(invalid)?;
+
+        Ok(Self { time, id })
+    }
 }
 
 impl Pagination {
     pub fn build_from_request_query(query: RequestQuery) -> PaginationBuilder {
+        Self::build_with_limit(query, LIMIT)
+    }
+
+    /// Same as `build_from_request_query` for feeds whose pages are larger than the default.
+    pub fn build_with_limit(query: RequestQuery, max_limit: u64) -> PaginationBuilder {
+        Self::build_with_default(query, max_limit, max_limit)
+    }
+
+    /// Pages of `default_limit` rows unless `?limit=` asks for more, up to `max_limit`.
+    pub fn build_with_default(
+        query: RequestQuery,
+        default_limit: u64,
+        max_limit: u64,
+    ) -> PaginationBuilder {
         let limit = query
             .limit
-            .map(|t| std::cmp::min(t, LIMIT))
-            .unwrap_or(LIMIT);
+            .map(|t| t.clamp(1, max_limit))
+            .unwrap_or(default_limit);
         let offset = query.offset.unwrap_or(OFFSET);
         PaginationBuilder {
-            count: None,
             limit,
             offset,
+            keyset: query.cursor.is_some(),
+            ..Default::default()
         }
     }
 }
@@ -36,6 +118,9 @@ pub struct PaginationBuilder {
     pub count: Option<usize>,
     pub limit: u64,
     pub offset: u64,
+    pub keyset: bool,
+    pub next_cursor: Option<Cursor>,
+    pub uri: Option<Uri>,
 }
 
 impl Default for PaginationBuilder {
@@ -44,6 +129,9 @@ impl Default for PaginationBuilder {
             count: None,
             limit: LIMIT,
             offset: OFFSET,
+            keyset: false,
+            next_cursor: None,
+            uri: None,
         }
     }
 }
@@ -54,11 +142,150 @@ impl PaginationBuilder {
         self
     }
 
+    /// Page by cursor rather than offset. `next` is the position of the last row returned,
+    /// ignored when the page came back short.
+    pub fn next_cursor(mut self, next: Option<Cursor>) -> Self {
+        self.keyset = true;
+        self.next_cursor = next;
+        self
+    }
+
+    /// The request uri, `Link` headers are only sent when it is set.
+    pub fn uri(mut self, uri: Uri) -> Self {
+        self.uri = Some(uri);
+        self
+    }
+
     pub fn build(self) -> Pagination {
+        let count = self.count.expect("Pagination count must to be set");
+        let full_page = count as u64 >= self.limit;
+
+        let next_cursor = self
+            .next_cursor
+            .filter(|_| self.keyset && full_page)
+            .map(|t| t.encode());
+
+        let link = self.uri.as_ref().map(|uri| {
+            let limit = self.limit.to_string();
+            let mut links = vec![link(uri, "first", &[("limit", &limit)])];
+
+            if self.keyset {
+                if let Some(cursor) = &next_cursor {
+                    links.push(link(uri, "next", &[("limit", &limit), ("cursor", cursor)]));
+                }
+            } else {
+                if self.offset > 0 {
+                    let prev = self.offset.saturating_sub(self.limit).to_string();
+                    links.push(link(uri, "prev", &[("limit", &limit), ("offset", &prev)]));
+                }
+                if full_page {
+                    let next = (self.offset + self.limit).to_string();
+                    links.push(link(uri, "next", &[("limit", &limit), ("offset", &next)]));
+                }
+            }
+
+            links.join(", ")
+        });
+
         Pagination {
-            count: self.count.expect("Pagination count must to be set"),
+            count,
             limit: self.limit,
-            offset: self.offset,
+            offset: if self.keyset { 0 } else { self.offset },
+            next_cursor,
+            link,
+        }
+    }
+}
+
+/// `uri` with its paging parameters replaced by `params`, as a RFC 8288 link.
+fn link(uri: &Uri, rel: &str, params: &[(&str, &str)]) -> String {
+    let mut query = url::form_urlencoded::Serializer::new(String::new());
+
+    for (key, value) in url::form_urlencoded::parse(uri.query().unwrap_or("").as_bytes()) {
+        if !matches!(key.as_ref(), "limit" | "offset" | "cursor") {
+            query.append_pair(&key, &value);
+        }
+    }
+    for (key, value) in params {
+        query.append_pair(key, value);
+    }
+
+    format!("<{}?{}>; rel=\"{rel}\"", uri.path(), query.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query(limit: u64, offset: u64) -> RequestQuery {
+        RequestQuery {
+            from: None,
+            limit: Some(limit),
+            offset: Some(offset),
+            cursor: None,
         }
     }
+
+    #[test]
+    fn cursor_round_trip() {
+        let time =
+            NaiveDateTime::parse_from_str("2023-10-20 08:12:00", "%Y-%m-%d %H:%M:%S").unwrap();
+        let id = Uuid::new_v4();
+
+        for cursor in [Cursor::new(time, Some(id)), Cursor::new(time, None)] {
+            assert_eq!(Cursor::decode(&cursor.encode()).unwrap(), cursor);
+        }
+        assert!(Cursor::decode("not a cursor").is_err());
+        assert!(Cursor::decode("zz").is_err());
+        assert!(Cursor::decode("313").is_err());
+    }
+
+    #[test]
+    fn offset_links_keep_other_params() {
+        let uri: Uri = "/api/stock/symbol?sort=asc&offset=20&limit=10"
+            .parse()
+            .unwrap();
+        let pagination = Pagination::build_from_request_query(query(10, 20))
+            .count(10)
+            .uri(uri)
+            .build();
+
+        assert_eq!(
+            pagination.link.unwrap(),
+            "</api/stock/symbol?sort=asc&limit=10>; rel=\"first\", \
+            </api/stock/symbol?sort=asc&limit=10&offset=10>; rel=\"prev\", \
+            </api/stock/symbol?sort=asc&limit=10&offset=30>; rel=\"next\""
+        );
+    }
+
+    #[test]
+    fn default_limit_can_be_raised() {
+        let mut no_limit = query(0, 0);
+        no_limit.limit = None;
+        assert_eq!(Pagination::build_with_default(no_limit, 10, 50).limit, 10);
+        assert_eq!(
+            Pagination::build_with_default(query(30, 0), 10, 50).limit,
+            30
+        );
+        assert_eq!(
+            Pagination::build_with_default(query(80, 0), 10, 50).limit,
+            50
+        );
+    }
+
+    #[test]
+    fn short_page_has_no_next() {
+        let time = NaiveDateTime::from_timestamp_micros(0).unwrap();
+        let pagination = Pagination::build_from_request_query(query(10, 0))
+            .count(3)
+            .next_cursor(Some(Cursor::new(time, None)))
+            .uri("/api/stock/news".parse().unwrap())
+            .build();
+
+        assert_eq!(pagination.next_cursor, None);
+        assert_eq!(
+            pagination.link.unwrap(),
+            "</api/stock/news?limit=10>; rel=\"first\""
+        );
+    }
 }
@@ -0,0 +1,32 @@
+use crate::error::{Error, Result};
+use redis::Client as RedisClient;
+
+/// Fixed-window counter kept in Redis. Fails with `TOOMANYREQUESTS` once `key`
+/// has been hit more than `limit` times within `window_secs`.
+pub async fn check(redis: &RedisClient, key: &str, limit: u64, window_secs: usize) -> Result<()> {
+    let mut con = redis.get_async_connection().await?;
+
+    // the window is opened and counted in one transaction, so a key is never left without
+    // its expiry
+    let (hits,): (u64,) = redis::pipe()
+        .atomic()
+        .cmd("SET")
+        .arg(key)
+        .arg(0)
+        .arg("NX")
+        .arg("EX")
+        .arg(window_secs)
+        .ignore()
+        .incr(key, 1)
+        .query_async(&mut con)
+        .await?;
+
+    if hits > limit {
+        return Err(Error::TOOMANYREQUESTS(format!(
+            "Too many requests, try again in {} minutes.",
+            window_secs / 60
+        )));
+    }
+
+    Ok(())
+}
@@ -1,7 +1,7 @@
 use crate::pagination::Pagination;
 use axum::{
     http::{
-        header::{self, HeaderName, HeaderValue},
+        header::{self, HeaderMap, HeaderName, HeaderValue},
         StatusCode,
     },
     response::{IntoResponse, Response as AxumResponse},
@@ -89,24 +89,29 @@ where
                 let count = pagination.count.to_string();
                 let offset = pagination.offset.to_string();
                 let limit = pagination.limit.to_string();
-                let headers = [
-                    (
-                        header::CONTENT_TYPE,
-                        HeaderValue::from_static(mime::APPLICATION_JSON.as_ref()),
-                    ),
-                    (
-                        HeaderName::from_static("x-pagination-count"),
-                        HeaderValue::from_str(&count).unwrap(),
-                    ),
-                    (
-                        HeaderName::from_static("x-pagination-offset"),
-                        HeaderValue::from_str(&offset).unwrap(),
-                    ),
-                    (
-                        HeaderName::from_static("x-pagination-limit"),
-                        HeaderValue::from_str(&limit).unwrap(),
-                    ),
-                ];
+                let mut headers = HeaderMap::new();
+                headers.insert(
+                    header::CONTENT_TYPE,
+                    HeaderValue::from_static(mime::APPLICATION_JSON.as_ref()),
+                );
+                headers.insert(
+                    HeaderName::from_static("x-pagination-count"),
+                    HeaderValue::from_str(&count).unwrap(),
+                );
+                headers.insert(
+                    HeaderName::from_static("x-pagination-offset"),
+                    HeaderValue::from_str(&offset).unwrap(),
+                );
+                headers.insert(
+                    HeaderName::from_static("x-pagination-limit"),
+                    HeaderValue::from_str(&limit).unwrap(),
+                );
+                if let Some(Ok(cursor)) = pagination.next_cursor.map(HeaderValue::try_from) {
+                    headers.insert(HeaderName::from_static("x-pagination-next-cursor"), cursor);
+                }
+                if let Some(Ok(link)) = pagination.link.map(HeaderValue::try_from) {
+                    headers.insert(header::LINK, link);
+                }
 
                 let bytes = bytes.into_inner().freeze();
                 (self.status_code, headers, bytes).into_response()
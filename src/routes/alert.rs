@@ -0,0 +1,300 @@
+use crate::alerting::{self, Condition};
+use crate::app_state::AppState;
+use crate::error::Error;
+use crate::model::alert::{Alert, AlertTrigger, CreatedAlert, SaveAlert, WebhookSecret};
+use crate::pagination::{Pagination, RequestQuery};
+use crate::response;
+use crate::session::CurrentUser;
+use axum::{
+    extract::{Json, OriginalUri, Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    routing::{get, post},
+    Router,
+};
+use validator::Validate;
+
+const MAX_ALERTS: i64 = 50;
+const DEFAULT_COOLDOWN_MINUTES: i32 = 24 * 60;
+const ALERT_COLUMNS: &str = "id, symbol, kind, direction, threshold, cooldown_minutes,
+    notify_email, webhook_url, active, last_triggered_at, created_at, updated_at";
+
+pub fn routes(app: &mut AppState) -> Router {
+    Router::new()
+        .route("/alerts", get(list_alerts).post(create_alert))
+        .route(
+            "/alerts/:id",
+            get(alert).put(update_alert).delete(delete_alert),
+        )
+        .route("/alerts/:id/secret", post(rotate_secret))
+        .route("/alerts/:id/triggers", get(list_triggers))
+        .with_state(app.clone())
+}
+
+/// The rule with its symbol upper-cased and an empty webhook url read as none.
+async fn check_rule(app: &AppState, body: &mut SaveAlert) -> Result<(), Error> {
+    body.symbol = body.symbol.trim().to_uppercase();
+    body.webhook_url = body.webhook_url.take().filter(|t| !t.trim().is_empty());
+    body.validate()?;
+
+    Condition::parse(
+        &body.kind,
+        body.direction.as_deref().unwrap_or("above"),
+        body.threshold,
+    )?;
+
+    if let Some(url) = &body.webhook_url {
+        alerting::check_webhook_url(url).await?;
+    }
+
+    let known = sqlx::query_as::<_, (String,)>(
+        "SELECT symbol FROM demo_app.stock_profile WHERE symbol = ($1)",
+    )
+    .bind(&body.symbol)
+    .fetch_optional(&app.db)
+    .await?;
+
+    if known.is_none() {
+        return Err(Error::NOTFOUND(format!("symbol : `{}`", body.symbol)));
+    }
+
+    Ok(())
+}
+
+// region: --- route /alerts
+async fn list_alerts(
+    user: CurrentUser,
+    State(app): State<AppState>,
+    OriginalUri(uri): OriginalUri,
+    Query(query): Query<RequestQuery>,
+) -> Result<impl IntoResponse, Error> {
+    let pagination = Pagination::build_from_request_query(query);
+
+    let alerts = sqlx::query_as::<_, Alert>(&format!(
+        "SELECT {ALERT_COLUMNS} FROM weblog.alert
+        WHERE usr_id = ($1)
+        ORDER BY created_at ASC, id ASC
+        LIMIT ($2) OFFSET ($3)"
+    ))
+    .bind(user.usr_id)
+    .bind(pagination.limit as i64)
+    .bind(pagination.offset as i64)
+    .fetch_all(&app.db)
+    .await?;
+
+    let pagination = pagination.count(alerts.len()).uri(uri).build();
+
+    let res = response::CustomResponseBuilder::new()
+        .body(alerts)
+        .pagination(pagination)
+        .build();
+
+    Ok(res)
+}
+
+async fn create_alert(
+    user: CurrentUser,
+    State(app): State<AppState>,
+    Json(mut body): Json<SaveAlert>,
+) -> Result<impl IntoResponse, Error> {
+    check_rule(&app, &mut body).await?;
+
+    let (owned,) =
+        sqlx::query_as::<_, (i64,)>("SELECT count(*) FROM weblog.alert WHERE usr_id = ($1)")
+            .bind(user.usr_id)
+            .fetch_one(&app.db)
+            .await?;
+
+    if owned >= MAX_ALERTS {
+        return Err(Error::BADREQUEST(format!(
+            "A user can have at most {MAX_ALERTS} alerts."
+        )));
+    }
+
+    let secret = alerting::new_secret();
+
+    let alert = sqlx::query_as::<_, Alert>(&format!(
+        "INSERT INTO weblog.alert(usr_id, symbol, kind, direction, threshold, cooldown_minutes,
+            notify_email, webhook_url, webhook_secret, active)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+        RETURNING {ALERT_COLUMNS}"
+    ))
+    .bind(user.usr_id)
+    .bind(&body.symbol)
+    .bind(&body.kind)
+    .bind(body.direction.as_deref().unwrap_or("above"))
+    .bind(body.threshold)
+    .bind(body.cooldown_minutes.unwrap_or(DEFAULT_COOLDOWN_MINUTES))
+    .bind(body.notify_email.unwrap_or(true))
+    .bind(&body.webhook_url)
+    .bind(&secret)
+    .bind(body.active.unwrap_or(true))
+    .fetch_one(&app.db)
+    .await?;
+
+    let res = response::CustomResponseBuilder::new()
+        .body(CreatedAlert {
+            alert,
+            webhook_secret: secret,
+        })
+        .status_code(StatusCode::CREATED)
+        .build();
+
+    Ok(res)
+}
+// endregion: --- route /alerts
+
+// region: --- route /alerts/:id
+async fn alert(
+    user: CurrentUser,
+    State(app): State<AppState>,
+    Path(id): Path<i32>,
+) -> Result<impl IntoResponse, Error> {
+    let alert = sqlx::query_as::<_, Alert>(&format!(
+        "SELECT {ALERT_COLUMNS} FROM weblog.alert WHERE id = ($1) AND usr_id = ($2)"
+    ))
+    .bind(id)
+    .bind(user.usr_id)
+    .fetch_optional(&app.db)
+    .await?;
+
+    match alert {
+        Some(t) => Ok(response::CustomResponseBuilder::new().body(t).build()),
+        None => Err(Error::NOTFOUND(format!("alert : `{id}`"))),
+    }
+}
+
+async fn update_alert(
+    user: CurrentUser,
+    State(app): State<AppState>,
+    Path(id): Path<i32>,
+    Json(mut body): Json<SaveAlert>,
+) -> Result<impl IntoResponse, Error> {
+    check_rule(&app, &mut body).await?;
+
+    // a changed rule starts over, it has to see the condition not hold before it fires
+    let alert = sqlx::query_as::<_, Alert>(&format!(
+        "UPDATE weblog.alert
+        SET symbol = ($1), kind = ($2), direction = ($3), threshold = ($4),
+            cooldown_minutes = ($5), notify_email = ($6), webhook_url = ($7), active = ($8),
+            last_state = NULL, last_evaluated_date = NULL, updated_at = current_timestamp
+        WHERE id = ($9) AND usr_id = ($10)
+        RETURNING {ALERT_COLUMNS}"
+    ))
+    .bind(&body.symbol)
+    .bind(&body.kind)
+    .bind(body.direction.as_deref().unwrap_or("above"))
+    .bind(body.threshold)
+    .bind(body.cooldown_minutes.unwrap_or(DEFAULT_COOLDOWN_MINUTES))
+    .bind(body.notify_email.unwrap_or(true))
+    .bind(&body.webhook_url)
+    .bind(body.active.unwrap_or(true))
+    .bind(id)
+    .bind(user.usr_id)
+    .fetch_optional(&app.db)
+    .await?;
+
+    match alert {
+        Some(t) => Ok(response::CustomResponseBuilder::new().body(t).build()),
+        None => Err(Error::NOTFOUND(format!("alert : `{id}`"))),
+    }
+}
+
+async fn delete_alert(
+    user: CurrentUser,
+    State(app): State<AppState>,
+    Path(id): Path<i32>,
+) -> Result<impl IntoResponse, Error> {
+    let deleted = sqlx::query("DELETE FROM weblog.alert WHERE id = ($1) AND usr_id = ($2)")
+        .bind(id)
+        .bind(user.usr_id)
+        .execute(&app.db)
+        .await?;
+
+    if deleted.rows_affected() == 0 {
+        return Err(Error::NOTFOUND(format!("alert : `{id}`")));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+// endregion: --- route /alerts/:id
+
+// region: --- route /alerts/:id/secret
+async fn rotate_secret(
+    user: CurrentUser,
+    State(app): State<AppState>,
+    Path(id): Path<i32>,
+) -> Result<impl IntoResponse, Error> {
+    let secret = alerting::new_secret();
+
+    let rotated = sqlx::query(
+        "UPDATE weblog.alert SET webhook_secret = ($1), updated_at = current_timestamp
+        WHERE id = ($2) AND usr_id = ($3)",
+    )
+    .bind(&secret)
+    .bind(id)
+    .bind(user.usr_id)
+    .execute(&app.db)
+    .await?;
+
+    if rotated.rows_affected() == 0 {
+        return Err(Error::NOTFOUND(format!("alert : `{id}`")));
+    }
+
+    let res = response::CustomResponseBuilder::new()
+        .body(WebhookSecret {
+            webhook_secret: secret,
+        })
+        .build();
+
+    Ok(res)
+}
+// endregion: --- route /alerts/:id/secret
+
+// region: --- route /alerts/:id/triggers
+async fn list_triggers(
+    user: CurrentUser,
+    State(app): State<AppState>,
+    Path(id): Path<i32>,
+    OriginalUri(uri): OriginalUri,
+    Query(query): Query<RequestQuery>,
+) -> Result<impl IntoResponse, Error> {
+    let owned = sqlx::query_as::<_, (i32,)>(
+        "SELECT id FROM weblog.alert WHERE id = ($1) AND usr_id = ($2)",
+    )
+    .bind(id)
+    .bind(user.usr_id)
+    .fetch_optional(&app.db)
+    .await?;
+
+    if owned.is_none() {
+        return Err(Error::NOTFOUND(format!("alert : `{id}`")));
+    }
+
+    let pagination = Pagination::build_from_request_query(query);
+
+    let triggers = sqlx::query_as::<_, AlertTrigger>(
+        "SELECT t.id, t.bar_date, t.payload, t.outbox_id, mo.status as email_status,
+            t.webhook_status, t.webhook_attempts, t.webhook_error, t.triggered_at
+        FROM weblog.alert_trigger t
+        LEFT JOIN weblog.mail_outbox mo ON mo.id = t.outbox_id
+        WHERE t.alert_id = ($1)
+        ORDER BY t.triggered_at DESC, t.id DESC
+        LIMIT ($2) OFFSET ($3)",
+    )
+    .bind(id)
+    .bind(pagination.limit as i64)
+    .bind(pagination.offset as i64)
+    .fetch_all(&app.db)
+    .await?;
+
+    let pagination = pagination.count(triggers.len()).uri(uri).build();
+
+    let res = response::CustomResponseBuilder::new()
+        .body(triggers)
+        .pagination(pagination)
+        .build();
+
+    Ok(res)
+}
+// endregion: --- route /alerts/:id/triggers
@@ -4,24 +4,29 @@ use crate::model::user;
 use crate::routes::WEBLOG_ID;
 use axum::{
     extract::{Query, State},
-    headers::{HeaderMap, HeaderValue},
-    http::{header, Request, StatusCode},
-    middleware::Next,
-    response::{IntoResponse, Redirect, Response},
+    response::{IntoResponse, Redirect},
     routing::get,
     Router,
 };
-use chrono::{prelude::*, Duration, NaiveDate};
+use chrono::{Duration, Utc};
 use oauth2::{
-    reqwest::async_http_client, AuthorizationCode, CsrfToken, PkceCodeChallenge, TokenResponse,
+    reqwest::async_http_client, AuthorizationCode, CsrfToken, PkceCodeChallenge, PkceCodeVerifier,
+    TokenResponse,
 };
+use redis::AsyncCommands;
 use reqwest::header::{ACCEPT, USER_AGENT};
 use time::{Duration as TimeDuration, OffsetDateTime};
 use tower_cookies::{
     cookie::{CookieBuilder, SameSite},
-    Cookie as TowerCookie, Cookies, Key,
+    Cookie as TowerCookie, Cookies,
 };
 use uuid::Uuid;
+
+// ties the GitHub redirect back to the browser that started the login
+const OAUTH_STATE: &str = "weblog_oauth_state";
+const OAUTH_STATE_SECS: usize = 10 * 60;
+const SESSION_HOURS: i64 = 7;
+
 pub fn routes(app: &mut AppState) -> Router {
     Router::new()
         .route("/auth/github", get(login_with_github))
@@ -29,17 +34,43 @@ pub fn routes(app: &mut AppState) -> Router {
         .with_state(app.clone())
 }
 
-pub async fn login_with_github(State(app): State<AppState>) -> impl IntoResponse {
+fn oauth_state_key(state: &str) -> String {
+    format!("oauth:state:{state}")
+}
+
+/// The PKCE verifier waits in Redis under the CSRF state until GitHub redirects back.
+pub async fn login_with_github(
+    cookie: Cookies,
+    State(app): State<AppState>,
+) -> Result<impl IntoResponse, Error> {
     let client = app.oauth_client;
 
-    let (pkce_code_challenge, _pkce_code_verifier) = PkceCodeChallenge::new_random_sha256();
+    let (pkce_code_challenge, pkce_code_verifier) = PkceCodeChallenge::new_random_sha256();
 
-    let (authorize_url, _) = client
+    let (authorize_url, csrf_state) = client
         .authorize_url(CsrfToken::new_random)
         .set_pkce_challenge(pkce_code_challenge)
         .url();
 
-    Redirect::to(authorize_url.as_ref())
+    let mut con = app.redis.get_async_connection().await?;
+    con.set_ex::<_, _, ()>(
+        oauth_state_key(csrf_state.secret()),
+        pkce_code_verifier.secret(),
+        OAUTH_STATE_SECS,
+    )
+    .await?;
+
+    // lax, the callback is a top level navigation coming from github.com
+    let ck = CookieBuilder::new(OAUTH_STATE, csrf_state.secret().to_string())
+        .path("/")
+        .max_age(TimeDuration::seconds(OAUTH_STATE_SECS as i64))
+        .http_only(true)
+        .same_site(SameSite::Lax)
+        .finish();
+
+    cookie.add(ck);
+
+    Ok(Redirect::to(authorize_url.as_ref()))
 }
 
 #[derive(Debug, serde::Deserialize)]
@@ -51,7 +82,6 @@ struct AuthRequest {
 #[derive(Debug, serde::Serialize, sqlx::FromRow)]
 struct User {
     usr_id: i32,
-    username: String,
 }
 
 #[derive(Debug, serde::Serialize, sqlx::FromRow)]
@@ -59,123 +89,122 @@ struct SessionId {
     ss_id: Uuid,
 }
 
-#[derive(Debug, serde::Serialize, sqlx::FromRow)]
-struct ExpireTime {
-    expires: NaiveDateTime,
-}
-
 async fn github_callback(
     cookie: Cookies,
     State(app): State<AppState>,
     Query(query): Query<AuthRequest>,
 ) -> Result<impl IntoResponse, Error> {
-    let mut headers = HeaderMap::new();
+    let state = query.state.secret();
+
+    let started_here = cookie
+        .get(OAUTH_STATE)
+        .is_some_and(|ck| ck.value() == state);
+    cookie.remove(TowerCookie::build(OAUTH_STATE, "").path("/").finish());
+
+    // the state is taken by the first callback that presents it
+    let key = oauth_state_key(state);
+    let mut con = app.redis.get_async_connection().await?;
+    let (verifier,): (Option<String>,) = redis::pipe()
+        .atomic()
+        .get(&key)
+        .del(&key)
+        .ignore()
+        .query_async(&mut con)
+        .await?;
+
+    let Some(verifier) = verifier.filter(|_| started_here) else {
+        return Err(Error::UNAUTHORIZED(
+            "Login state is invalid or has expired.".to_string(),
+        ));
+    };
 
     let client = app.oauth_client;
 
     let token = client
         .exchange_code(AuthorizationCode::new(query.code))
+        .set_pkce_verifier(PkceCodeVerifier::new(verifier))
         .request_async(async_http_client)
         .await
-        .unwrap();
+        .map_err(|e| Error::UNAUTHORIZED(format!("GitHub login failed: {e}")))?;
 
-    let access_token: Result<&String, std::convert::Infallible> =
-        token.access_token().secret().try_into();
+    let access_token = token.access_token().secret();
 
-    let reqwest = app
+    let res = app
         .client
         .get("https://api.github.com/user")
         .header(ACCEPT, "application/json")
-        .bearer_auth(access_token.unwrap().as_str())
-        .header(USER_AGENT, "Rust");
-
-    let res = reqwest
+        .bearer_auth(access_token)
+        .header(USER_AGENT, "Rust")
         .send()
-        .await
-        .unwrap()
+        .await?
+        .error_for_status()?
         .json::<user::GithubUser>()
         .await?;
 
-    let is_registered = sqlx::query_as::<_, User>(
-        r#"SELECT usr_id, username FROM weblog.user WHERE username = ($1)"#,
-    )
-    .bind(&res.login)
-    .fetch_optional(&app.db)
-    .await?;
-
-    let local = Local::now();
+    let is_registered =
+        sqlx::query_as::<_, User>(r#"SELECT usr_id FROM weblog.user WHERE github_id = ($1)"#)
+            .bind(res.id)
+            .fetch_optional(&app.db)
+            .await?;
 
-    let datetime = NaiveDate::from_ymd_opt(local.year(), local.month(), local.day())
-        .unwrap()
-        .and_hms_opt(local.hour(), local.minute(), local.second())
-        .unwrap()
-        + Duration::hours(7);
+    let expires = Utc::now() + Duration::hours(SESSION_HOURS);
 
     let mut transaction = app.db.begin().await?;
 
-    match is_registered {
+    let session_id = match is_registered {
         Some(user) => {
-            let ss_id = cookie.get(WEBLOG_ID);
-
-            if ss_id.is_none() {
-                let session_id = sqlx::query_as::<_, SessionId>(
-                    r#"
-                INSERT INTO weblog.session_table(expires, session) VALUES ($1, $2) RETURNING ss_id
-                "#,
-                )
-                .bind(&datetime)
-                .bind(&access_token.unwrap().as_str())
-                .fetch_one(&mut transaction)
-                .await?;
-
+            let session_id = sqlx::query_as::<_, SessionId>(
+                r#"INSERT INTO weblog.session_table(expires, session) VALUES ($1, $2) RETURNING ss_id"#,
+            )
+            .bind(expires)
+            .bind(access_token)
+            .fetch_one(&mut transaction)
+            .await?;
+
+            let updated =
                 sqlx::query(r#"UPDATE weblog.who_is_login SET ss_id = ($1) WHERE usr_id = ($2)"#)
-                    .bind(&session_id.ss_id.to_string())
-                    .bind(&user.usr_id)
+                    .bind(session_id.ss_id)
+                    .bind(user.usr_id)
                     .execute(&mut transaction)
                     .await?;
 
-                let expiry = OffsetDateTime::now_utc().checked_add(TimeDuration::hours(7));
-
-                let ck = CookieBuilder::new(WEBLOG_ID, session_id.ss_id.to_string())
-                    .expires(expiry)
-                    .http_only(true)
-                    .same_site(SameSite::Strict)
-                    .finish();
-
-                cookie.add(ck);
+            if updated.rows_affected() == 0 {
+                sqlx::query(r#"INSERT INTO weblog.who_is_login(usr_id, ss_id) VALUES ($1, $2)"#)
+                    .bind(user.usr_id)
+                    .bind(session_id.ss_id)
+                    .execute(&mut transaction)
+                    .await?;
             }
 
-            headers.insert(header::LOCATION, HeaderValue::from_static("/"));
-
-            Ok((StatusCode::FOUND, headers).into_response())
+            session_id
         }
         None => {
-            let session_id = sqlx::query_as::<_,SessionId>(
+            sqlx::query_as::<_,SessionId>(
                 r#"with ss as (INSERT INTO weblog.session_table(expires, session) VALUES ($1, $2) RETURNING ss_id)
-            ,usr as (INSERT INTO weblog.user(github_id, username, created_at, updated_at) VALUES ($3, $4, current_timestamp, current_timestamp) RETURNING usr_id)
+            ,usr as (INSERT INTO weblog.user(github_id, username, email, created_at, updated_at) VALUES ($3, $4, $5, current_timestamp, current_timestamp) RETURNING usr_id)
             INSERT INTO weblog.who_is_login(usr_id, ss_id) SELECT usr_id, ss_id FROM ss, usr RETURNING ss_id
             "#,
-            ).bind(&datetime)
-            .bind(&access_token.unwrap().as_str())
-            .bind(&res.id)
+            ).bind(expires)
+            .bind(access_token)
+            .bind(res.id)
             .bind(&res.login)
-            .fetch_one( &mut transaction).await.unwrap();
-
-            let expiry = OffsetDateTime::now_utc().checked_add(TimeDuration::hours(7));
+            .bind(&res.email)
+            .fetch_one( &mut transaction).await?
+        }
+    };
 
-            let ck = CookieBuilder::new(WEBLOG_ID, session_id.ss_id.to_string())
-                .expires(expiry)
-                .http_only(true)
-                .same_site(SameSite::Strict)
-                .finish();
+    transaction.commit().await?;
 
-            cookie.add(ck);
+    let expiry = OffsetDateTime::now_utc().checked_add(TimeDuration::hours(SESSION_HOURS));
 
-            headers.insert(header::LOCATION, HeaderValue::from_static("/"));
+    let ck = CookieBuilder::new(WEBLOG_ID, session_id.ss_id.to_string())
+        .path("/")
+        .expires(expiry)
+        .http_only(true)
+        .same_site(SameSite::Strict)
+        .finish();
 
-            transaction.commit().await?;
+    cookie.add(ck);
 
-            Ok((StatusCode::FOUND, headers).into_response())
-        }
-    }
+    Ok(Redirect::to("/"))
 }
@@ -1,36 +1,260 @@
-// use crate::app_state::AppState;
+use crate::app_state::AppState;
 use crate::error::Error;
-use crate::model::mail::Mail;
-use axum::{extract::Json, http::StatusCode, response::IntoResponse, routing::post, Router};
-use lettre::message::header::ContentType;
-use lettre::transport::smtp::authentication::Credentials;
-use lettre::{Message, SmtpTransport, Transport};
+use crate::model::mail::{ContactMessage, FormToken, Mail, QueueStats};
+use crate::outbox::{self, MailKind};
+use crate::pagination::{Pagination, RequestQuery};
+use crate::rate_limit;
+use crate::response;
+use crate::session::AdminUser;
+use crate::template::{self, Lang};
+use axum::{
+    extract::{ConnectInfo, Json, OriginalUri, Path, Query, State},
+    http::StatusCode,
+    response::{Html, IntoResponse},
+    routing::{get, post},
+    Router,
+};
+use chrono::Utc;
+use lettre::message::Mailbox;
+use lettre::Message;
+use redis::AsyncCommands;
+use serde_json::json;
+use std::net::SocketAddr;
+use uuid::Uuid;
+use validator::Validate;
 
-pub fn routes() -> Router {
-    Router::new().route("/mail", post(sendmail))
+// a person needs at least this long to fill in the form
+const MIN_SUBMIT_MILLIS: i64 = 3_000;
+const RATE_LIMIT: u64 = 5;
+const RATE_WINDOW_SECS: usize = 60 * 60;
+// an unsent form is given up on after this long
+const FORM_TOKEN_SECS: usize = 60 * 60 * 2;
+
+pub fn routes(app: &mut AppState) -> Router {
+    Router::new()
+        .route("/mail", post(sendmail))
+        .route("/mail/token", get(form_token))
+        .route("/admin/mail", get(list_messages))
+        .route("/admin/mail/queue", get(queue_stats))
+        .route("/admin/mail/templates", get(list_templates))
+        .route("/admin/mail/templates/:name", get(preview_template))
+        .route("/admin/mail/:id", get(message))
+        .with_state(app.clone())
+}
+
+// region: --- route /mail
+fn form_token_key(token: &Uuid) -> String {
+    format!("mail:form:{}", token.simple())
 }
 
-pub async fn sendmail(Json(ct): Json<Mail>) -> Result<impl IntoResponse, Error> {
+/// Issued when the contact form is rendered. The render time is kept in Redis so the
+/// submit can be timed without trusting the client's clock.
+async fn form_token(State(app): State<AppState>) -> Result<impl IntoResponse, Error> {
+    let token = Uuid::new_v4();
+
+    let mut con = app.redis.get_async_connection().await?;
+    con.set_ex::<_, _, ()>(
+        form_token_key(&token),
+        Utc::now().timestamp_millis(),
+        FORM_TOKEN_SECS,
+    )
+    .await?;
+
+    let res = response::CustomResponseBuilder::new()
+        .body(FormToken { form_token: token })
+        .build();
+
+    Ok(res)
+}
+
+pub async fn sendmail(
+    State(app): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Json(ct): Json<Mail>,
+) -> Result<impl IntoResponse, Error> {
+    ct.validate()?;
+
+    // a token is taken by the first submit that presents it
+    let key = form_token_key(&ct.form_token);
+    let mut con = app.redis.get_async_connection().await?;
+    let (rendered_at,): (Option<i64>,) = redis::pipe()
+        .atomic()
+        .get(&key)
+        .del(&key)
+        .ignore()
+        .query_async(&mut con)
+        .await?;
+    let Some(rendered_at) = rendered_at else {
+        return Err(Error::BADREQUEST(
+            "the form has expired, reload the page and try again.".to_string(),
+        ));
+    };
+
+    // answer bots as if the message went through so they don't retry
+    let elapsed = Utc::now().timestamp_millis() - rendered_at;
+    if ct.website.as_deref().is_some_and(|t| !t.is_empty()) || elapsed < MIN_SUBMIT_MILLIS {
+        return Ok(StatusCode::ACCEPTED);
+    }
+
+    let ip = addr.ip().to_string();
+    rate_limit::check(
+        &app.redis,
+        &format!("mail:rate:{ip}"),
+        RATE_LIMIT,
+        RATE_WINDOW_SECS,
+    )
+    .await?;
+
+    let from = ct
+        .from
+        .parse::<Mailbox>()
+        .map_err(|_| Error::BADREQUEST(format!("`{}` is not a valid email address.", ct.from)))?;
+
+    let rendered = template::render(
+        "contact",
+        app.mailer.recipient_lang,
+        json!({
+            "sender": &ct.from,
+            "subject": &ct.subject,
+            "body": &ct.body,
+            "ip_address": &ip,
+            "submitted_at": Utc::now().format("%Y-%m-%d %H:%M:%S UTC").to_string(),
+        }),
+    )?;
+
     let email = Message::builder()
-        .from(ct.from.parse().unwrap())
-        .to("<ancishih@gmail.com>".parse().unwrap())
-        .subject(ct.subject.as_str())
-        .header(ContentType::TEXT_PLAIN)
-        .body(String::from(ct.body))
-        .unwrap();
-
-    let creds = Credentials::new("ancishih".to_string(), "pflourlwhwxdjryi".to_string());
-
-    let mailer = SmtpTransport::relay("smtp.googlemail.com")
-        .unwrap()
-        .credentials(creds)
+        .from(from)
+        .to(app.mailer.recipient.clone())
+        .subject(rendered.subject.as_str())
+        .multipart(rendered.multipart())
+        .map_err(|e| Error::BADREQUEST(e.to_string()))?;
+
+    let mut transaction = app.db.begin().await?;
+
+    let outbox_id = outbox::enqueue(&mut transaction, MailKind::Contact, &email).await?;
+
+    sqlx::query(
+        "INSERT INTO weblog.contact_message(sender, subject, body, ip_address, outbox_id)
+        VALUES ($1, $2, $3, $4, $5)",
+    )
+    .bind(&ct.from)
+    .bind(&ct.subject)
+    .bind(&ct.body)
+    .bind(&ip)
+    .bind(outbox_id)
+    .execute(&mut transaction)
+    .await?;
+
+    transaction.commit().await?;
+
+    Ok(StatusCode::ACCEPTED)
+}
+// endregion: --- route /mail
+
+// region: --- route /admin/mail
+async fn list_messages(
+    _admin: AdminUser,
+    State(app): State<AppState>,
+    OriginalUri(uri): OriginalUri,
+    Query(query): Query<RequestQuery>,
+) -> Result<impl IntoResponse, Error> {
+    let pagination = Pagination::build_from_request_query(query);
+
+    let messages = sqlx::query_as::<_, ContactMessage>(
+        "SELECT cm.id, cm.sender, cm.subject, cm.body, cm.ip_address, cm.created_at,
+            coalesce(mo.status, 'unknown') as delivery_status, mo.last_error as delivery_error
+        FROM weblog.contact_message cm
+        LEFT JOIN weblog.mail_outbox mo ON mo.id = cm.outbox_id
+        ORDER BY cm.created_at DESC
+        LIMIT ($1) OFFSET ($2)",
+    )
+    .bind(pagination.limit as i64)
+    .bind(pagination.offset as i64)
+    .fetch_all(&app.db)
+    .await?;
+
+    let pagination = pagination.count(messages.len()).uri(uri).build();
+
+    let res = response::CustomResponseBuilder::new()
+        .body(messages)
+        .pagination(pagination)
         .build();
 
-    match mailer.send(&email) {
-        Ok(_t) => Ok((StatusCode::ACCEPTED).into_response()),
-        Err(e) => panic!("{:?}", e),
+    Ok(res)
+}
+
+async fn message(
+    _admin: AdminUser,
+    State(app): State<AppState>,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, Error> {
+    let message = sqlx::query_as::<_, ContactMessage>(
+        "SELECT cm.id, cm.sender, cm.subject, cm.body, cm.ip_address, cm.created_at,
+            coalesce(mo.status, 'unknown') as delivery_status, mo.last_error as delivery_error
+        FROM weblog.contact_message cm
+        LEFT JOIN weblog.mail_outbox mo ON mo.id = cm.outbox_id
+        WHERE cm.id = ($1)",
+    )
+    .bind(id)
+    .fetch_optional(&app.db)
+    .await?;
+
+    match message {
+        Some(t) => Ok(response::CustomResponseBuilder::new().body(t).build()),
+        None => Err(Error::NOTFOUND(format!("message : `{id}`"))),
     }
+}
+
+async fn queue_stats(
+    _admin: AdminUser,
+    State(app): State<AppState>,
+) -> Result<impl IntoResponse, Error> {
+    let stats = sqlx::query_as::<_, QueueStats>(
+        "SELECT count(*) FILTER (WHERE status = 'pending') as pending,
+            count(*) FILTER (WHERE status = 'sending') as sending,
+            count(*) FILTER (WHERE status = 'sent') as sent,
+            count(*) FILTER (WHERE status = 'dead') as dead,
+            min(created_at) FILTER (WHERE status IN ('pending', 'sending')) as oldest_pending,
+            max(last_error) FILTER (WHERE status = 'dead') as last_dead_error
+        FROM weblog.mail_outbox",
+    )
+    .fetch_one(&app.db)
+    .await?;
 
-    // doueyrtpgrpvpksh
-    // pflourlwhwxdjryi
+    let res = response::CustomResponseBuilder::new().body(stats).build();
+
+    Ok(res)
+}
+
+async fn list_templates(_admin: AdminUser) -> Result<impl IntoResponse, Error> {
+    let res = response::CustomResponseBuilder::new()
+        .body(template::NAMES)
+        .build();
+
+    Ok(res)
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct PreviewQuery {
+    lang: Option<String>,
+    format: Option<String>,
+}
+
+async fn preview_template(
+    _admin: AdminUser,
+    Path(name): Path<String>,
+    Query(query): Query<PreviewQuery>,
+) -> Result<impl IntoResponse, Error> {
+    let lang = query.lang.map(|t| Lang::from_tag(&t)).unwrap_or_default();
+
+    let rendered = template::render_sample(&name, lang)?;
+
+    match query.format.as_deref() {
+        None | Some("html") => Ok(Html(rendered.html).into_response()),
+        Some("text") => Ok(rendered.text.into_response()),
+        Some(other) => Err(Error::BADREQUEST(format!(
+            "format `{other}` is not one of html, text."
+        ))),
+    }
 }
+// endregion: --- route /admin/mail
@@ -0,0 +1,178 @@
+use crate::app_state::AppState;
+use crate::error::Error;
+use crate::market_calendar::{MarketCalendar, MarketPhase, Session, CLOSE};
+use crate::model::market::{IndexQuote, MarketOverview, MarketStatus, SessionTimes, Timestamp};
+use crate::response;
+use crate::routes::stock::{self, CandleQuery};
+use axum::{
+    extract::{Path, Query, State},
+    response::IntoResponse,
+    routing::get,
+    Router,
+};
+use chrono::{DateTime, Duration, NaiveDateTime, Offset, TimeZone, Utc};
+use chrono_tz::{America::New_York, Tz};
+
+pub fn routes(app: &mut AppState) -> Router {
+    Router::new()
+        .route("/market/status", get(market_status))
+        .route("/market/overview", get(market_overview))
+        .route("/indices", get(list_indices))
+        .route("/indices/:symbol/candles", get(index_candles))
+        .with_state(app.clone())
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct StatusQuery {
+    tz: Option<String>,
+}
+
+fn timestamp<Z: TimeZone>(at: DateTime<Z>, tz: Tz) -> Timestamp {
+    let local = at.with_timezone(&tz);
+    Timestamp {
+        utc: at.with_timezone(&Utc),
+        local: local.with_timezone(&local.offset().fix()),
+    }
+}
+
+fn session_times(session: Session, tz: Tz) -> SessionTimes {
+    SessionTimes {
+        date: session.date,
+        open: timestamp(session.open, tz),
+        close: timestamp(session.close, tz),
+        early_close: session.close.time() < CLOSE,
+    }
+}
+
+/// Every index with its latest quote and a sparkline of the last session, in seed order.
+async fn index_quotes(app: &AppState) -> Result<Vec<IndexQuote>, Error> {
+    let last_session = app.calendar.last_session(MarketCalendar::now());
+
+    let quotes = sqlx::query_as::<_, IndexQuote>(
+        "SELECT i.index_symbol as symbol, i.index_name as name, i.price, i.change,
+            i.change_percent, i.day_low, i.day_high, i.previous_close, i.quoted_at,
+            coalesce(spark.points, '{}') as sparkline
+        FROM demo_app.indices i
+        LEFT JOIN LATERAL (
+            SELECT array_agg(b.close ORDER BY b.bucket) as points
+            FROM (
+                SELECT time_bucket('15 minutes', time) as bucket, last(close, time) as close
+                FROM demo_app.index_price
+                WHERE symbol = i.index_symbol AND time >= ($1)
+                GROUP BY bucket
+            ) b
+        ) spark ON true
+        ORDER BY i.id ASC",
+    )
+    .bind(last_session.open_local())
+    .fetch_all(&app.db)
+    .await?;
+
+    Ok(quotes)
+}
+
+// region: --- route /market/status
+async fn market_status(
+    State(app): State<AppState>,
+    Query(query): Query<StatusQuery>,
+) -> Result<impl IntoResponse, Error> {
+    let tz = match query.tz.as_deref() {
+        Some(name) => name
+            .parse::<Tz>()
+            .map_err(|_| Error::BADREQUEST(format!("`{name}` is not an IANA timezone.")))?,
+        None => Tz::UTC,
+    };
+
+    let now = MarketCalendar::now();
+    let phase = app.calendar.phase(now);
+
+    // stock_price.time holds New York wall-clock time
+    let (latest,) =
+        sqlx::query_as::<_, (Option<NaiveDateTime>,)>("SELECT max(time) FROM demo_app.stock_price")
+            .fetch_one(&app.db)
+            .await?;
+    let latest_bar = latest.and_then(|t| New_York.from_local_datetime(&t).earliest());
+
+    // minute bars are stamped at their start, the last one of a session is a minute before the close
+    let expected = match phase {
+        MarketPhase::Open => now,
+        _ => app.calendar.last_session(now).close,
+    } - Duration::minutes(1);
+    let data_delayed = !matches!(latest_bar, Some(t) if t >= expected);
+
+    let status = MarketStatus {
+        phase,
+        is_open: phase == MarketPhase::Open,
+        timezone: tz.name().to_string(),
+        exchange_timezone: New_York.name().to_string(),
+        now: timestamp(now, tz),
+        session: app
+            .calendar
+            .session(now.date_naive())
+            .map(|t| session_times(t, tz)),
+        next_session: session_times(app.calendar.next_open(now), tz),
+        latest_bar: latest_bar.map(|t| timestamp(t, tz)),
+        data_delayed,
+    };
+
+    let res = response::CustomResponseBuilder::new().body(status).build();
+
+    Ok(res)
+}
+// endregion: --- route /market/status
+
+// region: --- route /market/overview
+async fn market_overview(State(app): State<AppState>) -> Result<impl IntoResponse, Error> {
+    let last_session = app.calendar.last_session(MarketCalendar::now());
+
+    let overview = MarketOverview {
+        indices: index_quotes(&app).await?,
+        sectors: stock::top_five_each_sector(&app.db, last_session.open_local()).await?,
+    };
+
+    let res = response::CustomResponseBuilder::new()
+        .body(overview)
+        .build();
+
+    Ok(res)
+}
+// endregion: --- route /market/overview
+
+// region: --- route /indices
+async fn list_indices(State(app): State<AppState>) -> Result<impl IntoResponse, Error> {
+    let indices = index_quotes(&app).await?;
+
+    let res = response::CustomResponseBuilder::new().body(indices).build();
+
+    Ok(res)
+}
+
+async fn index_candles(
+    State(app): State<AppState>,
+    Path(symbol): Path<String>,
+    Query(query): Query<CandleQuery>,
+) -> Result<impl IntoResponse, Error> {
+    // `^GSPC`, `GSPC` and the url-encoded `%5EGSPC` all name the S&P 500
+    let index = sqlx::query_as::<_, (String,)>(
+        "SELECT index_symbol FROM demo_app.indices
+        WHERE upper(index_symbol) IN (upper($1), '^' || upper($1)) OR symbol_with_html = ($1)",
+    )
+    .bind(&symbol)
+    .fetch_optional(&app.db)
+    .await?;
+
+    let Some((index,)) = index else {
+        return Err(Error::NOTFOUND(format!("index : `{symbol}`")));
+    };
+
+    // indices only have minute rows, the daily and longer candles are bucketed from them
+    let candles = stock::load_candles(&app.db, index, query, |_| {
+        "select time, open, high, low, close, volume from demo_app.index_price where symbol = ($2)"
+    })
+    .await?;
+
+    let res = response::CustomResponseBuilder::new().body(candles).build();
+
+    Ok(res)
+}
+// endregion: --- route /indices
@@ -1,5 +1,9 @@
+pub mod alert;
 pub mod auth;
-pub mod cron;
 pub mod mail;
+pub mod market;
+pub mod portfolio;
 pub mod stock;
+pub mod user;
+pub mod watchlist;
 pub const WEBLOG_ID: &str = "weblog_id";
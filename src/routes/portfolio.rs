@@ -0,0 +1,662 @@
+use crate::app_state::AppState;
+use crate::error::Error;
+use crate::ledger::{self, CostMethod};
+use crate::model::portfolio::{
+    Holding, ImportResult, NewTransaction, Portfolio, PortfolioSummary, PortfolioTransaction,
+    SavePortfolio, SectorAllocation, Valuation,
+};
+use crate::pagination::{Pagination, RequestQuery};
+use crate::response;
+use crate::session::CurrentUser;
+use axum::{
+    extract::{Json, OriginalUri, Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    routing::{delete, get, post},
+    Router,
+};
+use chrono::{Duration, NaiveDate, Utc};
+use serde::Deserialize;
+use sqlx::{PgConnection, PgExecutor, PgPool};
+use std::collections::{BTreeSet, HashMap};
+use validator::Validate;
+
+const MAX_PORTFOLIOS: i64 = 20;
+const HISTORY_DAYS: i64 = 365;
+const MAX_HISTORY_DAYS: i64 = 5 * 366;
+const PORTFOLIO_COLUMNS: &str = "id, name, cost_method, created_at, updated_at";
+
+pub fn routes(app: &mut AppState) -> Router {
+    Router::new()
+        .route("/portfolios", get(list_portfolios).post(create_portfolio))
+        .route(
+            "/portfolios/:id",
+            get(portfolio_summary)
+                .put(update_portfolio)
+                .delete(delete_portfolio),
+        )
+        .route(
+            "/portfolios/:id/transactions",
+            get(list_transactions).post(add_transaction),
+        )
+        .route(
+            "/portfolios/:id/transactions/import",
+            post(import_transactions),
+        )
+        .route(
+            "/portfolios/:id/transactions/:tx_id",
+            delete(delete_transaction),
+        )
+        .route("/portfolios/:id/history", get(portfolio_history))
+        .route("/portfolios/:id/allocation", get(portfolio_allocation))
+        .with_state(app.clone())
+}
+
+fn is_unique_violation(e: &sqlx::Error) -> bool {
+    matches!(e, sqlx::Error::Database(e) if e.code().as_deref() == Some("23505"))
+}
+
+/// The portfolio if `usr_id` owns it. Someone else's portfolio is reported as missing.
+async fn owned_portfolio<'e, E>(executor: E, usr_id: i32, id: i32) -> Result<Portfolio, Error>
+where
+    E: PgExecutor<'e>,
+{
+    let portfolio = sqlx::query_as::<_, Portfolio>(&format!(
+        "SELECT {PORTFOLIO_COLUMNS} FROM weblog.portfolio WHERE id = ($1) AND usr_id = ($2)"
+    ))
+    .bind(id)
+    .bind(usr_id)
+    .fetch_optional(executor)
+    .await?;
+
+    portfolio.ok_or_else(|| Error::NOTFOUND(format!("portfolio : `{id}`")))
+}
+
+/// Same as `owned_portfolio`, holding the row until the transaction ends so concurrent
+/// writes replay one after the other.
+async fn lock_portfolio(conn: &mut PgConnection, usr_id: i32, id: i32) -> Result<Portfolio, Error> {
+    let portfolio = sqlx::query_as::<_, Portfolio>(&format!(
+        "SELECT {PORTFOLIO_COLUMNS} FROM weblog.portfolio
+        WHERE id = ($1) AND usr_id = ($2) FOR UPDATE"
+    ))
+    .bind(id)
+    .bind(usr_id)
+    .fetch_optional(conn)
+    .await?;
+
+    portfolio.ok_or_else(|| Error::NOTFOUND(format!("portfolio : `{id}`")))
+}
+
+async fn transactions<'e, E>(executor: E, id: i32) -> Result<Vec<PortfolioTransaction>, Error>
+where
+    E: PgExecutor<'e>,
+{
+    let transactions = sqlx::query_as::<_, PortfolioTransaction>(
+        "SELECT id, symbol, kind, trade_date, quantity, price, fee, created_at
+        FROM weblog.portfolio_transaction
+        WHERE portfolio_id = ($1)
+        ORDER BY trade_date ASC, id ASC",
+    )
+    .bind(id)
+    .fetch_all(executor)
+    .await?;
+
+    Ok(transactions)
+}
+
+/// Replays the whole portfolio after a write, so a change that leaves a sell without the
+/// shares behind it is refused before it is committed.
+async fn check_book(conn: &mut PgConnection, portfolio: &Portfolio) -> Result<(), Error> {
+    let method = portfolio.cost_method.parse::<CostMethod>()?;
+    ledger::replay(method, &transactions(&mut *conn, portfolio.id).await?)?;
+
+    sqlx::query("UPDATE weblog.portfolio SET updated_at = current_timestamp WHERE id = ($1)")
+        .bind(portfolio.id)
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
+
+async fn check_symbols(db: &PgPool, symbols: &BTreeSet<String>) -> Result<(), Error> {
+    let symbols: Vec<&str> = symbols.iter().map(String::as_str).collect();
+
+    let known = sqlx::query_as::<_, (String,)>(
+        "SELECT DISTINCT symbol FROM demo_app.stock_profile WHERE symbol = ANY(($1)::varchar[])",
+    )
+    .bind(&symbols)
+    .fetch_all(db)
+    .await?;
+
+    let unknown: Vec<&str> = symbols
+        .into_iter()
+        .filter(|t| !known.iter().any(|(k,)| k == t))
+        .collect();
+
+    match unknown.as_slice() {
+        [] => Ok(()),
+        [symbol] => Err(Error::NOTFOUND(format!("symbol : `{symbol}`"))),
+        _ => Err(Error::BADREQUEST(format!(
+            "unknown symbols: {}.",
+            unknown.join(", ")
+        ))),
+    }
+}
+
+/// Latest price for each symbol: the newest `stock_price` close, or the `stock_profile`
+/// snapshot for symbols without one.
+async fn valuations(db: &PgPool, symbols: &[String]) -> Result<HashMap<String, Valuation>, Error> {
+    let valuations = sqlx::query_as::<_, Valuation>(
+        "SELECT s.symbol, sp.company_name, ss.sector_name as sector,
+            coalesce(lp.close, sp.price) as price, lp.time as priced_at,
+            lp.close IS NOT NULL as from_close
+        FROM unnest(($1)::varchar[]) AS s(symbol)
+        LEFT JOIN LATERAL (
+            SELECT company_name, price, sector_id FROM demo_app.stock_profile
+            WHERE symbol = s.symbol LIMIT 1
+        ) sp ON true
+        LEFT JOIN demo_app.stock_sector ss ON ss.sector_id = sp.sector_id
+        LEFT JOIN LATERAL (
+            SELECT time, close FROM demo_app.stock_price
+            WHERE symbol = s.symbol AND close IS NOT NULL
+            ORDER BY time DESC LIMIT 1
+        ) lp ON true",
+    )
+    .bind(symbols)
+    .fetch_all(db)
+    .await?;
+
+    Ok(valuations
+        .into_iter()
+        .map(|t| (t.symbol.clone(), t))
+        .collect())
+}
+
+async fn summary(db: &PgPool, portfolio: Portfolio) -> Result<PortfolioSummary, Error> {
+    let method = portfolio.cost_method.parse::<CostMethod>()?;
+    let positions = ledger::replay(method, &transactions(db, portfolio.id).await?)?.positions();
+
+    let symbols: Vec<String> = positions.iter().map(|t| t.symbol.clone()).collect();
+    let mut valuations = valuations(db, &symbols).await?;
+
+    let holdings: Vec<Holding> = positions
+        .into_iter()
+        .map(|p| {
+            let valuation = valuations.remove(&p.symbol);
+            let price = valuation.as_ref().and_then(|t| t.price).map(f64::from);
+            let market_value = price.map(|t| t * p.quantity);
+            let unrealized = market_value.map(|t| t - p.cost_basis);
+
+            Holding {
+                company_name: valuation.as_ref().and_then(|t| t.company_name.clone()),
+                sector: valuation.as_ref().and_then(|t| t.sector.clone()),
+                average_cost: (p.quantity > 0.0).then(|| p.cost_basis / p.quantity),
+                price,
+                price_source: valuation.as_ref().filter(|t| t.price.is_some()).map(|t| {
+                    match t.from_close {
+                        true => "close",
+                        false => "snapshot",
+                    }
+                }),
+                priced_at: valuation.and_then(|t| t.priced_at),
+                market_value,
+                unrealized,
+                unrealized_percent: unrealized
+                    .filter(|_| p.cost_basis > 0.0)
+                    .map(|t| t / p.cost_basis * 100.0),
+                symbol: p.symbol,
+                quantity: p.quantity,
+                cost_basis: p.cost_basis,
+                realized: p.realized,
+                dividends: p.dividends,
+            }
+        })
+        .collect();
+
+    // a position without any price counts at cost
+    let market_value = holdings
+        .iter()
+        .map(|t| t.market_value.unwrap_or(t.cost_basis))
+        .sum();
+    let cost_basis: f64 = holdings.iter().map(|t| t.cost_basis).sum();
+
+    Ok(PortfolioSummary {
+        portfolio,
+        market_value,
+        cost_basis,
+        unrealized: market_value - cost_basis,
+        realized: holdings.iter().map(|t| t.realized).sum(),
+        dividends: holdings.iter().map(|t| t.dividends).sum(),
+        holdings,
+    })
+}
+
+// region: --- route /portfolios
+async fn list_portfolios(
+    user: CurrentUser,
+    State(app): State<AppState>,
+    OriginalUri(uri): OriginalUri,
+    Query(query): Query<RequestQuery>,
+) -> Result<impl IntoResponse, Error> {
+    let pagination = Pagination::build_from_request_query(query);
+
+    let portfolios = sqlx::query_as::<_, Portfolio>(&format!(
+        "SELECT {PORTFOLIO_COLUMNS} FROM weblog.portfolio
+        WHERE usr_id = ($1)
+        ORDER BY created_at ASC, id ASC
+        LIMIT ($2) OFFSET ($3)"
+    ))
+    .bind(user.usr_id)
+    .bind(pagination.limit as i64)
+    .bind(pagination.offset as i64)
+    .fetch_all(&app.db)
+    .await?;
+
+    let pagination = pagination.count(portfolios.len()).uri(uri).build();
+
+    let res = response::CustomResponseBuilder::new()
+        .body(portfolios)
+        .pagination(pagination)
+        .build();
+
+    Ok(res)
+}
+
+async fn create_portfolio(
+    user: CurrentUser,
+    State(app): State<AppState>,
+    Json(body): Json<SavePortfolio>,
+) -> Result<impl IntoResponse, Error> {
+    body.validate()?;
+
+    let method = match body.cost_method.as_deref() {
+        Some(t) => t.parse::<CostMethod>()?,
+        None => CostMethod::Fifo,
+    };
+
+    let (owned,) =
+        sqlx::query_as::<_, (i64,)>("SELECT count(*) FROM weblog.portfolio WHERE usr_id = ($1)")
+            .bind(user.usr_id)
+            .fetch_one(&app.db)
+            .await?;
+
+    if owned >= MAX_PORTFOLIOS {
+        return Err(Error::BADREQUEST(format!(
+            "A user can have at most {MAX_PORTFOLIOS} portfolios."
+        )));
+    }
+
+    let portfolio = sqlx::query_as::<_, Portfolio>(&format!(
+        "INSERT INTO weblog.portfolio(usr_id, name, cost_method) VALUES ($1, $2, $3)
+        RETURNING {PORTFOLIO_COLUMNS}"
+    ))
+    .bind(user.usr_id)
+    .bind(body.name.trim())
+    .bind(method.as_str())
+    .fetch_one(&app.db)
+    .await
+    .map_err(|e| match is_unique_violation(&e) {
+        true => Error::BADREQUEST(format!("portfolio `{}` already exists.", body.name.trim())),
+        false => e.into(),
+    })?;
+
+    let res = response::CustomResponseBuilder::new()
+        .body(portfolio)
+        .status_code(StatusCode::CREATED)
+        .build();
+
+    Ok(res)
+}
+// endregion: --- route /portfolios
+
+// region: --- route /portfolios/:id
+async fn portfolio_summary(
+    user: CurrentUser,
+    State(app): State<AppState>,
+    Path(id): Path<i32>,
+) -> Result<impl IntoResponse, Error> {
+    let portfolio = owned_portfolio(&app.db, user.usr_id, id).await?;
+
+    let summary = summary(&app.db, portfolio).await?;
+
+    let res = response::CustomResponseBuilder::new().body(summary).build();
+
+    Ok(res)
+}
+
+async fn update_portfolio(
+    user: CurrentUser,
+    State(app): State<AppState>,
+    Path(id): Path<i32>,
+    Json(body): Json<SavePortfolio>,
+) -> Result<impl IntoResponse, Error> {
+    body.validate()?;
+
+    let method = body
+        .cost_method
+        .as_deref()
+        .map(|t| t.parse::<CostMethod>())
+        .transpose()?;
+
+    let portfolio = sqlx::query_as::<_, Portfolio>(&format!(
+        "UPDATE weblog.portfolio
+        SET name = ($1), cost_method = coalesce(($2), cost_method), updated_at = current_timestamp
+        WHERE id = ($3) AND usr_id = ($4)
+        RETURNING {PORTFOLIO_COLUMNS}"
+    ))
+    .bind(body.name.trim())
+    .bind(method.map(|t| t.as_str()))
+    .bind(id)
+    .bind(user.usr_id)
+    .fetch_optional(&app.db)
+    .await
+    .map_err(|e| match is_unique_violation(&e) {
+        true => Error::BADREQUEST(format!("portfolio `{}` already exists.", body.name.trim())),
+        false => e.into(),
+    })?;
+
+    match portfolio {
+        Some(t) => Ok(response::CustomResponseBuilder::new().body(t).build()),
+        None => Err(Error::NOTFOUND(format!("portfolio : `{id}`"))),
+    }
+}
+
+async fn delete_portfolio(
+    user: CurrentUser,
+    State(app): State<AppState>,
+    Path(id): Path<i32>,
+) -> Result<impl IntoResponse, Error> {
+    let deleted = sqlx::query("DELETE FROM weblog.portfolio WHERE id = ($1) AND usr_id = ($2)")
+        .bind(id)
+        .bind(user.usr_id)
+        .execute(&app.db)
+        .await?;
+
+    if deleted.rows_affected() == 0 {
+        return Err(Error::NOTFOUND(format!("portfolio : `{id}`")));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+// endregion: --- route /portfolios/:id
+
+// region: --- route /portfolios/:id/transactions
+async fn list_transactions(
+    user: CurrentUser,
+    State(app): State<AppState>,
+    Path(id): Path<i32>,
+    OriginalUri(uri): OriginalUri,
+    Query(query): Query<RequestQuery>,
+) -> Result<impl IntoResponse, Error> {
+    owned_portfolio(&app.db, user.usr_id, id).await?;
+
+    let pagination = Pagination::build_from_request_query(query);
+
+    let transactions = sqlx::query_as::<_, PortfolioTransaction>(
+        "SELECT id, symbol, kind, trade_date, quantity, price, fee, created_at
+        FROM weblog.portfolio_transaction
+        WHERE portfolio_id = ($1)
+        ORDER BY trade_date DESC, id DESC
+        LIMIT ($2) OFFSET ($3)",
+    )
+    .bind(id)
+    .bind(pagination.limit as i64)
+    .bind(pagination.offset as i64)
+    .fetch_all(&app.db)
+    .await?;
+
+    let pagination = pagination.count(transactions.len()).uri(uri).build();
+
+    let res = response::CustomResponseBuilder::new()
+        .body(transactions)
+        .pagination(pagination)
+        .build();
+
+    Ok(res)
+}
+
+async fn add_transaction(
+    user: CurrentUser,
+    State(app): State<AppState>,
+    Path(id): Path<i32>,
+    Json(body): Json<NewTransaction>,
+) -> Result<impl IntoResponse, Error> {
+    let kind = ledger::check(&body)?;
+    let symbol = body.symbol.trim().to_uppercase();
+
+    check_symbols(&app.db, &BTreeSet::from([symbol.clone()])).await?;
+
+    let mut transaction = app.db.begin().await?;
+
+    let portfolio = lock_portfolio(&mut transaction, user.usr_id, id).await?;
+
+    let added = sqlx::query_as::<_, PortfolioTransaction>(
+        "INSERT INTO weblog.portfolio_transaction
+            (portfolio_id, symbol, kind, trade_date, quantity, price, fee)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        RETURNING id, symbol, kind, trade_date, quantity, price, fee, created_at",
+    )
+    .bind(id)
+    .bind(&symbol)
+    .bind(kind.as_str())
+    .bind(body.trade_date)
+    .bind(body.quantity)
+    .bind(body.price)
+    .bind(body.fee.unwrap_or(0.0))
+    .fetch_one(&mut transaction)
+    .await?;
+
+    check_book(&mut transaction, &portfolio).await?;
+
+    transaction.commit().await?;
+
+    let res = response::CustomResponseBuilder::new()
+        .body(added)
+        .status_code(StatusCode::CREATED)
+        .build();
+
+    Ok(res)
+}
+
+/// Takes the CSV file as the request body, see `ledger::parse_csv` for the columns.
+/// Either every row is imported or none is.
+async fn import_transactions(
+    user: CurrentUser,
+    State(app): State<AppState>,
+    Path(id): Path<i32>,
+    body: String,
+) -> Result<impl IntoResponse, Error> {
+    let rows = ledger::parse_csv(&body)?;
+
+    if rows.is_empty() {
+        return Err(Error::BADREQUEST(
+            "the file has no transactions.".to_string(),
+        ));
+    }
+
+    check_symbols(&app.db, &rows.iter().map(|t| t.symbol.clone()).collect()).await?;
+
+    let mut transaction = app.db.begin().await?;
+
+    let portfolio = lock_portfolio(&mut transaction, user.usr_id, id).await?;
+
+    // ordinality keeps the file order in the ids, which break ties between same-day trades
+    sqlx::query(
+        "INSERT INTO weblog.portfolio_transaction
+            (portfolio_id, symbol, kind, trade_date, quantity, price, fee)
+        SELECT ($1), t.symbol, t.kind, t.trade_date, t.quantity, t.price, t.fee
+        FROM unnest(($2)::varchar[], ($3)::varchar[], ($4)::date[],
+            ($5)::float8[], ($6)::float8[], ($7)::float8[])
+            WITH ORDINALITY AS t(symbol, kind, trade_date, quantity, price, fee, ord)
+        ORDER BY t.ord",
+    )
+    .bind(id)
+    .bind(rows.iter().map(|t| t.symbol.as_str()).collect::<Vec<_>>())
+    .bind(rows.iter().map(|t| t.kind.as_str()).collect::<Vec<_>>())
+    .bind(rows.iter().map(|t| t.trade_date).collect::<Vec<_>>())
+    .bind(rows.iter().map(|t| t.quantity).collect::<Vec<_>>())
+    .bind(rows.iter().map(|t| t.price).collect::<Vec<_>>())
+    .bind(
+        rows.iter()
+            .map(|t| t.fee.unwrap_or(0.0))
+            .collect::<Vec<_>>(),
+    )
+    .execute(&mut transaction)
+    .await?;
+
+    check_book(&mut transaction, &portfolio).await?;
+
+    transaction.commit().await?;
+
+    let res = response::CustomResponseBuilder::new()
+        .body(ImportResult {
+            imported: rows.len(),
+        })
+        .status_code(StatusCode::CREATED)
+        .build();
+
+    Ok(res)
+}
+
+async fn delete_transaction(
+    user: CurrentUser,
+    State(app): State<AppState>,
+    Path((id, tx_id)): Path<(i32, i32)>,
+) -> Result<impl IntoResponse, Error> {
+    let mut transaction = app.db.begin().await?;
+
+    let portfolio = lock_portfolio(&mut transaction, user.usr_id, id).await?;
+
+    let deleted = sqlx::query(
+        "DELETE FROM weblog.portfolio_transaction WHERE id = ($1) AND portfolio_id = ($2)",
+    )
+    .bind(tx_id)
+    .bind(id)
+    .execute(&mut transaction)
+    .await?;
+
+    if deleted.rows_affected() == 0 {
+        return Err(Error::NOTFOUND(format!("transaction : `{tx_id}`")));
+    }
+
+    // removing a buy can leave a later sell short
+    check_book(&mut transaction, &portfolio).await?;
+
+    transaction.commit().await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+// endregion: --- route /portfolios/:id/transactions
+
+// region: --- route /portfolios/:id/history
+#[derive(Debug, Deserialize)]
+struct HistoryQuery {
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+}
+
+async fn portfolio_history(
+    user: CurrentUser,
+    State(app): State<AppState>,
+    Path(id): Path<i32>,
+    Query(query): Query<HistoryQuery>,
+) -> Result<impl IntoResponse, Error> {
+    let portfolio = owned_portfolio(&app.db, user.usr_id, id).await?;
+    let method = portfolio.cost_method.parse::<CostMethod>()?;
+
+    let to = query.to.unwrap_or_else(|| Utc::now().date_naive());
+    let from = query.from.unwrap_or(to - Duration::days(HISTORY_DAYS));
+
+    if from > to {
+        return Err(Error::BADREQUEST(
+            "`from` must not be after `to`.".to_string(),
+        ));
+    }
+    if (to - from).num_days() > MAX_HISTORY_DAYS {
+        return Err(Error::BADREQUEST(format!(
+            "the range can span at most {MAX_HISTORY_DAYS} days."
+        )));
+    }
+
+    let transactions = transactions(&app.db, id).await?;
+    let symbols: Vec<String> = transactions
+        .iter()
+        .map(|t| t.symbol.clone())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+
+    // the last close before `from` seeds the prices carried into the range
+    let bars = sqlx::query_as::<_, (String, NaiveDate, f32)>(
+        "(SELECT DISTINCT ON (symbol) symbol, date, close FROM demo_app.daily_bar
+            WHERE symbol = ANY(($1)::text[]) AND date < ($2)
+            ORDER BY symbol, date DESC)
+        UNION ALL
+        (SELECT symbol, date, close FROM demo_app.daily_bar
+            WHERE symbol = ANY(($1)::text[]) AND date BETWEEN ($2) AND ($3))",
+    )
+    .bind(&symbols)
+    .bind(from)
+    .bind(to)
+    .fetch_all(&app.db)
+    .await?;
+
+    let mut dates = BTreeSet::new();
+    let mut closes: HashMap<String, Vec<(NaiveDate, f64)>> = HashMap::new();
+    for (symbol, date, close) in bars {
+        if date >= from {
+            dates.insert(date);
+        }
+        closes.entry(symbol).or_default().push((date, close as f64));
+    }
+    for series in closes.values_mut() {
+        series.sort_by_key(|t| t.0);
+    }
+
+    let dates: Vec<NaiveDate> = dates.into_iter().collect();
+    let history = ledger::history(method, &transactions, &dates, &closes)?;
+
+    let res = response::CustomResponseBuilder::new().body(history).build();
+
+    Ok(res)
+}
+// endregion: --- route /portfolios/:id/history
+
+// region: --- route /portfolios/:id/allocation
+async fn portfolio_allocation(
+    user: CurrentUser,
+    State(app): State<AppState>,
+    Path(id): Path<i32>,
+) -> Result<impl IntoResponse, Error> {
+    let portfolio = owned_portfolio(&app.db, user.usr_id, id).await?;
+
+    let summary = summary(&app.db, portfolio).await?;
+
+    let mut sectors: HashMap<Option<String>, f64> = HashMap::new();
+    for holding in summary.holdings.iter().filter(|t| t.quantity > 0.0) {
+        *sectors.entry(holding.sector.clone()).or_default() +=
+            holding.market_value.unwrap_or(holding.cost_basis);
+    }
+
+    let total: f64 = sectors.values().sum();
+    let mut allocation: Vec<SectorAllocation> = sectors
+        .into_iter()
+        .map(|(sector, market_value)| SectorAllocation {
+            sector,
+            market_value,
+            weight: if total > 0.0 {
+                market_value / total
+            } else {
+                0.0
+            },
+        })
+        .collect();
+    allocation.sort_by(|a, b| b.market_value.total_cmp(&a.market_value));
+
+    let res = response::CustomResponseBuilder::new()
+        .body(allocation)
+        .build();
+
+    Ok(res)
+}
+// endregion: --- route /portfolios/:id/allocation
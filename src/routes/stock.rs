@@ -1,21 +1,29 @@
+use crate::comparison;
 use crate::error::Error;
+use crate::indicator::{self, IndicatorKind};
+use crate::market_calendar::MarketCalendar;
+use crate::model::screener::ScreenRequest;
 use crate::model::stock;
+use crate::pagination::{Cursor, Pagination, RequestQuery};
 use crate::response;
+use crate::routine::DAILY_PERIODS;
+use crate::screener;
+use crate::search;
 use crate::{app_state::AppState, model::stock::TimeseriesDataBuilder};
 use axum::{
-    extract::{Json, Path, Query, State},
+    extract::{Json, OriginalUri, Path, Query, State},
     http::StatusCode,
     response::IntoResponse,
     routing::{get, post},
     Router,
 };
-use chrono::prelude::*;
-use chrono::{Duration, Weekday};
+use chrono::{Duration, NaiveDate, NaiveDateTime};
 use reqwest::header::{ACCEPT, USER_AGENT};
 use scraper::{Html, Selector};
 use serde::{de, Deserialize, Deserializer};
 use serde_json::json;
 use serde_with::{serde_as, NoneAsEmptyString};
+use sqlx::PgPool;
 use std::any::type_name;
 use std::{fmt, str::FromStr};
 use std::{fs, io::BufWriter};
@@ -26,9 +34,14 @@ pub fn routes(app: &mut AppState) -> Router {
         .route("/stock/sectors", get(list_sectors))
         .route("/stock/symbol", get(list_symbols))
         .route("/stock/symbol/:symbol", get(search_symbol))
+        .route("/stock/typeahead", get(typeahead))
+        .route("/stock/screener", post(screen))
         .route("/stock/news", get(list_internal_news))
         .route("/stock/news/:id", get(internal_news).post(other_news))
         .route("/stock/:symbol/news", get(symbol_news))
+        .route("/stock/:symbol/candles", get(symbol_candles))
+        .route("/stock/:symbol/indicators", get(symbol_indicators))
+        .route("/stock/compare", get(compare))
         .route("/stock/ext_news", get(list_external_news))
         .route("/stock/income_statement/:symbol", get(income_statement))
         .route("/stock/balance_sheet/:symbol", get(balance_sheet))
@@ -49,23 +62,36 @@ pub fn routes(app: &mut AppState) -> Router {
         .with_state(app.clone())
 }
 
-#[derive(Debug, serde::Deserialize)]
-struct Params {
-    from: i32,
-    to: i32,
-}
+// news pages were capped at 50 before pagination, keep that as the page size
+const NEWS_PAGE: u64 = 50;
+// the symbol news query returned the latest 10, keep that unless `?limit=` asks for more
+const SYMBOL_NEWS_PAGE: u64 = 10;
+// one regular session is 390 minute bars, a page holds all of it
+const PRICE_PAGE: u64 = 500;
 
 // region: --- route /stock/sectors
-async fn list_sectors(State(app): State<AppState>) -> Result<impl IntoResponse, Error> {
+async fn list_sectors(
+    State(app): State<AppState>,
+    OriginalUri(uri): OriginalUri,
+    Query(query): Query<RequestQuery>,
+) -> Result<impl IntoResponse, Error> {
+    let pagination = Pagination::build_from_request_query(query);
+
     let sectors = sqlx::query_as::<_, stock::Sectors>(
-        "SELECT sector_id, sector_name from demo_app.stock_sector ORDER BY sector_id ASC",
+        "SELECT sector_id, sector_name from demo_app.stock_sector ORDER BY sector_id ASC
+        LIMIT ($1) OFFSET ($2)",
     )
+    .bind(pagination.limit as i64)
+    .bind(pagination.offset as i64)
     .fetch_all(&app.db)
     .await?;
 
+    let pagination = pagination.count(sectors.len()).uri(uri).build();
+
     let res = response::CustomResponseBuilder::new()
         .body(sectors)
         .status_code(StatusCode::OK)
+        .pagination(pagination)
         .build();
 
     Ok(res)
@@ -73,15 +99,30 @@ async fn list_sectors(State(app): State<AppState>) -> Result<impl IntoResponse,
 // endregion: --- route /stock/sectors
 
 // region: --- route /stock/symbol
-async fn list_symbols(State(app): State<AppState>) -> Result<impl IntoResponse, Error> {
+async fn list_symbols(
+    State(app): State<AppState>,
+    OriginalUri(uri): OriginalUri,
+    Query(query): Query<RequestQuery>,
+) -> Result<impl IntoResponse, Error> {
+    let pagination = Pagination::build_from_request_query(query);
+
     let symbols = sqlx::query_as::<_, stock::Symbols>(
         "SELECT symbol, company_name, img 
         FROM demo_app.stock_profile 
-        ORDER BY symbol ASC",
+        ORDER BY symbol ASC
+        LIMIT ($1) OFFSET ($2)",
     )
+    .bind(pagination.limit as i64)
+    .bind(pagination.offset as i64)
     .fetch_all(&app.db)
     .await?;
-    let res = response::CustomResponseBuilder::new().body(symbols).build();
+
+    let pagination = pagination.count(symbols.len()).uri(uri).build();
+
+    let res = response::CustomResponseBuilder::new()
+        .body(symbols)
+        .pagination(pagination)
+        .build();
 
     Ok(res)
 }
@@ -89,40 +130,76 @@ async fn list_symbols(State(app): State<AppState>) -> Result<impl IntoResponse,
 async fn search_symbol(
     State(app): State<AppState>,
     Path(symbol): Path<String>,
-    Query(params): Query<Params>,
+    OriginalUri(uri): OriginalUri,
+    Query(query): Query<RequestQuery>,
 ) -> Result<impl IntoResponse, Error> {
-    let s = format!("{}", symbol).to_uppercase() + "%";
-
-    let search = sqlx::query_as::<_, stock::SymbolLists>(
-        "with symbol_info as (
-                select symbol, company_name, img
-                from demo_app.stock_profile 
-                where symbol like ($1) or company_name like ($2)
-                group by symbol, company_name, img
-                order by symbol asc
-                offset ($3) rows fetch first ($4) row only
-                ),
-            count_row as (
-                select count(*) as total_row from demo_app.stock_profile
-                where symbol like ($1) or company_name like ($2)
-                )
-            select jsonb_build_object('result',jsonb_agg(si.*), 'pagination', 
-            jsonb_build_object('total_rows', count_row.total_row, 'row_count', count(si.*))) as symbol_list, 
-            count_row.total_row as total
-            from symbol_info si, count_row
-            group by total
-            ",
+    let pagination = Pagination::build_from_request_query(query);
+
+    let q = search::normalize(&symbol)?;
+
+    let matches = search::search(
+        &app.db,
+        &q,
+        pagination.limit as i64,
+        pagination.offset as i64,
     )
-    .bind(&s)
-    .bind(&s)
-    .bind(&params.from)
-    .bind(&params.to).fetch_one(&app.db).await?;
+    .await?;
+
+    let pagination = pagination.count(matches.len()).uri(uri).build();
+
+    let res = response::CustomResponseBuilder::new()
+        .body(matches)
+        .pagination(pagination)
+        .build();
+    Ok(res)
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct TypeaheadQuery {
+    q: String,
+}
 
-    let res = response::CustomResponseBuilder::new().body(search).build();
+async fn typeahead(
+    State(app): State<AppState>,
+    Query(query): Query<TypeaheadQuery>,
+) -> Result<impl IntoResponse, Error> {
+    let q = search::normalize(&query.q)?;
+
+    let matches = search::typeahead(&app, &q).await?;
+
+    let res = response::CustomResponseBuilder::new().body(matches).build();
     Ok(res)
 }
 // endregion: --- route /stock/
 
+// region: --- route /stock/screener
+async fn screen(
+    State(app): State<AppState>,
+    OriginalUri(uri): OriginalUri,
+    Query(query): Query<RequestQuery>,
+    Json(body): Json<ScreenRequest>,
+) -> Result<impl IntoResponse, Error> {
+    let pagination = Pagination::build_from_request_query(query);
+
+    let rows = screener::screen(
+        &app.db,
+        &body,
+        pagination.limit as i64,
+        pagination.offset as i64,
+    )
+    .await?;
+
+    let pagination = pagination.count(rows.len()).uri(uri).build();
+
+    let res = response::CustomResponseBuilder::new()
+        .body(rows)
+        .pagination(pagination)
+        .build();
+
+    Ok(res)
+}
+// endregion: --- route /stock/screener
+
 // region: --- route /stock/profile/:symbol
 async fn profile(
     State(app): State<AppState>,
@@ -157,13 +234,34 @@ async fn symbol_price_daily(
 ) -> Result<impl IntoResponse, Error> {
     let s = format!("{symbol}").to_uppercase();
 
+    let periods: Vec<i32> = DAILY_PERIODS.iter().map(|t| *t as i32).collect();
+
     let stock_price = sqlx::query_as::<_, stock::DailyPrice2>(
-        "with daily_cte as (SELECT open, close, high, low, volume, ema, sma, bucket as time FROM daily_intraday_record WHERE symbol = ($1) ORDER BY  bucket ASC)
-        select jsonb_build_object('data',jsonb_agg(dc.*), 'symbol', ($1)) as result
-        from daily_cte dc 
+        "with daily_cte as (
+            select db.open, db.close, db.high, db.low, db.volume,
+                array(
+                    select coalesce(di.value, 'NaN') from unnest(($2)::int4[]) with ordinality as p(period, ord)
+                    left join demo_app.daily_indicator di on di.symbol = db.symbol and di.date = db.date
+                        and di.indicator = 'ema' and di.params = jsonb_build_object('period', p.period)
+                    order by p.ord
+                ) as ema,
+                array(
+                    select coalesce(di.value, 'NaN') from unnest(($2)::int4[]) with ordinality as p(period, ord)
+                    left join demo_app.daily_indicator di on di.symbol = db.symbol and di.date = db.date
+                        and di.indicator = 'sma' and di.params = jsonb_build_object('period', p.period)
+                    order by p.ord
+                ) as sma,
+                db.date::timestamp as time
+            from demo_app.daily_bar db
+            where db.symbol = ($1)
+            order by db.date asc
+        )
+        select jsonb_build_object('data', jsonb_agg(dc.*), 'symbol', ($1)) as result
+        from daily_cte dc
         ",
     )
     .bind(&s)
+    .bind(&periods)
     .fetch_one(&app.db)
     .await?;
 
@@ -179,61 +277,352 @@ async fn symbol_price_daily(
 async fn symbol_price(
     State(app): State<AppState>,
     Path(symbol): Path<String>,
+    OriginalUri(uri): OriginalUri,
+    Query(query): Query<RequestQuery>,
 ) -> Result<impl IntoResponse, Error> {
-    let local = Local::now();
-
-    let last_intraday_in_us = match local.weekday() {
-        Weekday::Mon => {
-            NaiveDate::from_ymd_opt(local.year(), local.month(), local.day())
-                .unwrap()
-                .and_hms_opt(9, 30, 0)
-                .unwrap()
-                - Duration::days(3)
-        }
-        Weekday::Sun => {
-            NaiveDate::from_ymd_opt(local.year(), local.month(), local.day())
-                .unwrap()
-                .and_hms_opt(9, 30, 0)
-                .unwrap()
-                - Duration::days(2)
-        }
-        _ => {
-            NaiveDate::from_ymd_opt(local.year(), local.month(), local.day())
-                .unwrap()
-                .and_hms_opt(9, 30, 0)
-                .unwrap()
-                - Duration::days(1)
-        }
-    };
+    let cursor = Cursor::from_query(&query)?;
+    let pagination = Pagination::build_with_limit(query, PRICE_PAGE);
+
+    let last_session = app.calendar.last_session(MarketCalendar::now());
 
     let s = format!("{symbol}").to_uppercase();
 
     let price = sqlx::query_as::<_, stock::DailyPriceView>(
         "select time, symbol, open, close, high, low, volume
         from demo_app.stock_price
-        where symbol = ($1) and time >= ($2) and ema is null
+        where symbol = ($1) and time >= ($2)
+            and (($3)::timestamp is null or time > ($3))
         order by time asc
+        limit ($4)
         ",
     )
     .bind(&s)
+    .bind(last_session.open_local())
+    .bind(cursor.map(|t| t.time))
+    .bind(pagination.limit as i64)
     .fetch_all(&app.db)
     .await?;
 
-    let res = response::CustomResponseBuilder::new().body(price).build();
+    let pagination = pagination
+        .count(price.len())
+        .next_cursor(price.last().map(|t| Cursor::new(t.time, None)))
+        .uri(uri)
+        .build();
+
+    let res = response::CustomResponseBuilder::new()
+        .body(price)
+        .pagination(pagination)
+        .build();
 
     Ok(res)
 }
 // endregion: --- route /stock/price/:symbol
 
+// region: --- route /stock/:symbol/candles
+const CANDLE_LIMIT: i64 = 500;
+const MAX_CANDLES: i64 = 2000;
+
+#[derive(Debug, serde::Deserialize)]
+pub(crate) struct CandleQuery {
+    interval: Option<String>,
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+    limit: Option<i64>,
+}
+
+async fn symbol_candles(
+    State(app): State<AppState>,
+    Path(symbol): Path<String>,
+    Query(query): Query<CandleQuery>,
+) -> Result<impl IntoResponse, Error> {
+    // intraday candles come from the minute rows, the rest from the daily bars
+    let candles = load_candles(&app.db, symbol.to_uppercase(), query, |interval| {
+        if interval.is_intraday() {
+            "select time, open, high, low, close, volume from demo_app.stock_price where symbol = ($2)"
+        } else {
+            "select date::timestamp as time, open, high, low, close, volume from demo_app.daily_bar where symbol = ($2)"
+        }
+    })
+    .await?;
+
+    // an empty range is fine for a known symbol, an unknown one is not found
+    if candles.candles.is_empty() {
+        let known = sqlx::query_as::<_, (String,)>(
+            "SELECT symbol FROM demo_app.stock_profile WHERE symbol = ($1)",
+        )
+        .bind(&candles.symbol)
+        .fetch_optional(&app.db)
+        .await?;
+
+        if known.is_none() {
+            return Err(Error::NOTFOUND(format!("symbol : `{}`", candles.symbol)));
+        }
+    }
+
+    let res = response::CustomResponseBuilder::new().body(candles).build();
+
+    Ok(res)
+}
+
+/// Candles of `symbol` bucketed out of the rows `source` selects for the interval, a query
+/// of time, open, high, low, close and volume filtered on `symbol = ($2)`.
+pub(crate) async fn load_candles(
+    db: &PgPool,
+    symbol: String,
+    query: CandleQuery,
+    source: fn(&stock::CandleInterval) -> &'static str,
+) -> Result<stock::Candles, Error> {
+    let interval = query
+        .interval
+        .as_deref()
+        .unwrap_or("1d")
+        .parse::<stock::CandleInterval>()?;
+
+    if let (Some(from), Some(to)) = (query.from, query.to) {
+        if from > to {
+            return Err(Error::BADREQUEST(
+                "`from` must not be after `to`.".to_string(),
+            ));
+        }
+    }
+
+    let limit = query.limit.unwrap_or(CANDLE_LIMIT).clamp(1, MAX_CANDLES);
+
+    // `to` is inclusive, the minute rows hold New York wall-clock time
+    let from = query.from.map(|t| t.and_hms_opt(0, 0, 0).unwrap());
+    let to = query
+        .to
+        .map(|t| (t + Duration::days(1)).and_hms_opt(0, 0, 0).unwrap());
+
+    // newest buckets first so the limit keeps the most recent end of the range
+    let mut candles = sqlx::query_as::<_, stock::Candle>(&format!(
+        "select time_bucket(($1)::interval, time) as time,
+            first(open, time) as open, max(high) as high, min(low) as low,
+            last(close, time) as close, sum(volume) as volume
+        from ({}) bars
+        where (($3)::timestamp is null or time >= ($3))
+            and (($4)::timestamp is null or time < ($4))
+        group by 1
+        order by 1 desc
+        limit ($5)",
+        source(&interval)
+    ))
+    .bind(interval.bucket())
+    .bind(&symbol)
+    .bind(from)
+    .bind(to)
+    .bind(limit)
+    .fetch_all(db)
+    .await?;
+
+    candles.reverse();
+
+    Ok(stock::Candles {
+        symbol,
+        interval,
+        candles,
+    })
+}
+// endregion: --- route /stock/:symbol/candles
+
+// region: --- route /stock/compare
+const COMPARE_DAYS: i64 = 365;
+// minute rows over longer ranges are better compared on daily bars
+const MAX_INTRADAY_COMPARE_DAYS: i64 = 31;
+
+#[derive(Debug, serde::Deserialize)]
+struct CompareQuery {
+    symbols: String,
+    interval: Option<String>,
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+}
+
+async fn compare(
+    State(app): State<AppState>,
+    Query(query): Query<CompareQuery>,
+) -> Result<impl IntoResponse, Error> {
+    let interval = query
+        .interval
+        .as_deref()
+        .unwrap_or("1d")
+        .parse::<stock::CandleInterval>()?;
+    let symbols = comparison::parse_symbols(&query.symbols)?;
+
+    // intraday defaults to the last session, the rest to a year up to `to`
+    let today = MarketCalendar::now().date_naive();
+    let from = match query.from {
+        Some(t) => t,
+        None if interval.is_intraday() => query
+            .to
+            .unwrap_or(app.calendar.last_session(MarketCalendar::now()).date),
+        None => query.to.unwrap_or(today) - Duration::days(COMPARE_DAYS),
+    };
+
+    if from > query.to.unwrap_or(today) {
+        return Err(Error::BADREQUEST(
+            "`from` must not be after `to`.".to_string(),
+        ));
+    }
+    if interval.is_intraday()
+        && query.to.unwrap_or(today) - from > Duration::days(MAX_INTRADAY_COMPARE_DAYS)
+    {
+        return Err(Error::BADREQUEST(format!(
+            "intraday intervals can compare at most {MAX_INTRADAY_COMPARE_DAYS} days."
+        )));
+    }
+
+    // `to` is inclusive, stock_price.time is New York wall-clock time
+    let from = from.and_hms_opt(0, 0, 0).unwrap();
+    let to = query
+        .to
+        .map(|t| (t + Duration::days(1)).and_hms_opt(0, 0, 0).unwrap());
+
+    let source = if interval.is_intraday() {
+        "select symbol, time, close from demo_app.stock_price
+        where symbol = any($2) and close > 0"
+    } else {
+        "select symbol, date::timestamp as time, close from demo_app.daily_bar
+        where symbol = any($2) and close > 0"
+    };
+
+    let rows = sqlx::query_as::<_, (String, NaiveDateTime, f32)>(&format!(
+        "select symbol, time_bucket(($1)::interval, time) as time, last(close, time) as close
+        from ({source}) bars
+        where time >= ($3) and (($4)::timestamp is null or time < ($4))
+        group by 1, 2
+        order by 2"
+    ))
+    .bind(interval.bucket())
+    .bind(&symbols)
+    .bind(from)
+    .bind(to)
+    .fetch_all(&app.db)
+    .await?;
+
+    let rows: Vec<(String, NaiveDateTime, f64)> = rows
+        .into_iter()
+        .map(|(symbol, time, close)| (symbol, time, close as f64))
+        .collect();
+
+    let (times, series) = comparison::align(&symbols, &rows, interval.periods_per_year())?;
+
+    let res = response::CustomResponseBuilder::new()
+        .body(stock::Comparison {
+            interval,
+            times,
+            series,
+        })
+        .build();
+
+    Ok(res)
+}
+// endregion: --- route /stock/compare
+
+// region: --- route /stock/:symbol/indicators
+const INDICATOR_LIMIT: usize = 250;
+const MAX_INDICATOR_POINTS: usize = 1000;
+
+#[derive(Debug, serde::Deserialize)]
+struct IndicatorQuery {
+    #[serde(rename = "type")]
+    kind: String,
+    period: Option<usize>,
+    limit: Option<usize>,
+}
+
+async fn symbol_indicators(
+    State(app): State<AppState>,
+    Path(symbol): Path<String>,
+    Query(query): Query<IndicatorQuery>,
+) -> Result<impl IntoResponse, Error> {
+    let kind = query.kind.parse::<IndicatorKind>()?;
+    // MACD always runs on 12/26/9
+    let period = match kind {
+        IndicatorKind::Macd => kind.default_period(),
+        _ => query.period.unwrap_or(kind.default_period()),
+    };
+    indicator::check_period(period)?;
+
+    let limit = query
+        .limit
+        .unwrap_or(INDICATOR_LIMIT)
+        .clamp(1, MAX_INDICATOR_POINTS);
+
+    let s = symbol.to_uppercase();
+
+    let mut bars = sqlx::query_as::<_, stock::DailyBar>(
+        "select symbol, date, open, high, low, close, volume
+        from demo_app.daily_bar
+        where symbol = ($1)
+        order by date desc
+        limit ($2)",
+    )
+    .bind(&s)
+    .bind((limit + kind.lookback(period)) as i64)
+    .fetch_all(&app.db)
+    .await?;
+
+    if bars.is_empty() {
+        return Err(Error::NOTFOUND(format!("daily prices of `{s}`")));
+    }
+
+    bars.reverse();
+
+    let high: Vec<f64> = bars.iter().map(|t| t.high as f64).collect();
+    let low: Vec<f64> = bars.iter().map(|t| t.low as f64).collect();
+    let close: Vec<f64> = bars.iter().map(|t| t.close as f64).collect();
+
+    let values = indicator::compute(
+        kind,
+        period,
+        &indicator::Series {
+            high: &high,
+            low: &low,
+            close: &close,
+        },
+    )?;
+
+    let mut points: Vec<stock::IndicatorPoint> = bars
+        .iter()
+        .zip(values)
+        .filter_map(|(bar, value)| {
+            Some(stock::IndicatorPoint {
+                date: bar.date,
+                value: value?,
+            })
+        })
+        .collect();
+    points.drain(..points.len().saturating_sub(limit));
+
+    let res = response::CustomResponseBuilder::new()
+        .body(stock::IndicatorSeries {
+            symbol: s,
+            kind,
+            period,
+            values: points,
+        })
+        .build();
+
+    Ok(res)
+}
+// endregion: --- route /stock/:symbol/indicators
+
 // region: --- route /stock/price/gainer
-async fn most_gainer(State(app): State<AppState>) -> Result<impl IntoResponse, Error> {
+pub async fn top_gainers(db: &PgPool) -> Result<Vec<stock::Gainer>, Error> {
     let gainer = sqlx::query_as::<_, stock::Gainer>(
         "SELECT symbol, company_name, price, change FROM demo_app.stock_profile 
         ORDER BY ROUND(CAST(change/(price-change)*100 as numeric),2) DESC LIMIT 10",
     )
-    .fetch_all(&app.db)
+    .fetch_all(db)
     .await?;
 
+    Ok(gainer)
+}
+
+async fn most_gainer(State(app): State<AppState>) -> Result<impl IntoResponse, Error> {
+    let gainer = top_gainers(&app.db).await?;
+
     let res = response::CustomResponseBuilder::new().body(gainer).build();
 
     Ok(res)
@@ -241,14 +630,20 @@ async fn most_gainer(State(app): State<AppState>) -> Result<impl IntoResponse, E
 // endregion: --- route /stock/price/gainer
 
 // region: --- route /stock/price/loser
-async fn most_loser(State(app): State<AppState>) -> Result<impl IntoResponse, Error> {
-    let gainer = sqlx::query_as::<_, stock::Gainer>(
+pub async fn top_losers(db: &PgPool) -> Result<Vec<stock::Gainer>, Error> {
+    let loser = sqlx::query_as::<_, stock::Gainer>(
         "SELECT symbol, company_name, price, change FROM demo_app.stock_profile 
         ORDER BY ROUND(CAST(change/(price-change)*100 as numeric),2) ASC LIMIT 10",
     )
-    .fetch_all(&app.db)
+    .fetch_all(db)
     .await?;
 
+    Ok(loser)
+}
+
+async fn most_loser(State(app): State<AppState>) -> Result<impl IntoResponse, Error> {
+    let gainer = top_losers(&app.db).await?;
+
     let res = response::CustomResponseBuilder::new().body(gainer).build();
 
     Ok(res)
@@ -261,35 +656,11 @@ struct SectorId {
 }
 
 // region: --- route /stock/price/sector
-async fn list_top_five_mkt_stock_price_each_sector(
-    State(app): State<AppState>,
-) -> Result<impl IntoResponse, Error> {
-    let local = Local::now();
-
-    let last_intraday_in_us = match local.weekday() {
-        Weekday::Mon => {
-            NaiveDate::from_ymd_opt(local.year(), local.month(), local.day())
-                .unwrap()
-                .and_hms_opt(9, 30, 0)
-                .unwrap()
-                - Duration::days(3)
-        }
-        Weekday::Sun => {
-            NaiveDate::from_ymd_opt(local.year(), local.month(), local.day())
-                .unwrap()
-                .and_hms_opt(9, 30, 0)
-                .unwrap()
-                - Duration::days(2)
-        }
-        _ => {
-            NaiveDate::from_ymd_opt(local.year(), local.month(), local.day())
-                .unwrap()
-                .and_hms_opt(9, 30, 0)
-                .unwrap()
-                - Duration::days(1)
-        }
-    };
-
+/// The five largest companies of every sector with their minute closes since `since`.
+pub async fn top_five_each_sector(
+    db: &PgPool,
+    since: NaiveDateTime,
+) -> Result<Vec<stock::StockPriceEachSector>, Error> {
     let price = sqlx::query_as::<_, stock::StockPriceEachSector>(
         "with mkt_cap_cte as (
             select sp.symbol, sp.company_name, sp.mkt_cap, sp.sector_id, sect.sector_name,
@@ -309,10 +680,20 @@ async fn list_top_five_mkt_stock_price_each_sector(
         order by ts.sector_id asc;
         ",
     )
-    .bind(&last_intraday_in_us)
-    .fetch_all(&app.db)
+    .bind(since)
+    .fetch_all(db)
     .await?;
 
+    Ok(price)
+}
+
+async fn list_top_five_mkt_stock_price_each_sector(
+    State(app): State<AppState>,
+) -> Result<impl IntoResponse, Error> {
+    let last_session = app.calendar.last_session(MarketCalendar::now());
+
+    let price = top_five_each_sector(&app.db, last_session.open_local()).await?;
+
     let res = response::CustomResponseBuilder::new().body(price).build();
 
     Ok(res)
@@ -322,31 +703,7 @@ async fn top_five_mkt_stock_price(
     State(app): State<AppState>,
     Json(id): Json<SectorId>,
 ) -> Result<impl IntoResponse, Error> {
-    let local = Local::now();
-
-    let last_intraday_in_us = match local.weekday() {
-        Weekday::Mon => {
-            NaiveDate::from_ymd_opt(local.year(), local.month(), local.day())
-                .unwrap()
-                .and_hms_opt(9, 30, 0)
-                .unwrap()
-                - Duration::days(3)
-        }
-        Weekday::Sun => {
-            NaiveDate::from_ymd_opt(local.year(), local.month(), local.day())
-                .unwrap()
-                .and_hms_opt(9, 30, 0)
-                .unwrap()
-                - Duration::days(2)
-        }
-        _ => {
-            NaiveDate::from_ymd_opt(local.year(), local.month(), local.day())
-                .unwrap()
-                .and_hms_opt(9, 30, 0)
-                .unwrap()
-                - Duration::days(1)
-        }
-    };
+    let last_session = app.calendar.last_session(MarketCalendar::now());
 
     let price = sqlx::query_as::<_, stock::StockPriceGroupBySector>(
         "
@@ -366,7 +723,7 @@ async fn top_five_mkt_stock_price(
     ",
     )
     .bind(&id.sector_id)
-    .bind(&last_intraday_in_us)
+    .bind(last_session.open_local())
     .fetch_all(&app.db)
     .await?;
 
@@ -381,13 +738,39 @@ struct Limit {
     number: f32,
 }
 
-async fn list_internal_news(State(app): State<AppState>) -> Result<impl IntoResponse, Error> {
+async fn list_internal_news(
+    State(app): State<AppState>,
+    OriginalUri(uri): OriginalUri,
+    Query(query): Query<RequestQuery>,
+) -> Result<impl IntoResponse, Error> {
+    let cursor = Cursor::from_query(&query)?;
+    let pagination = Pagination::build_with_limit(query, NEWS_PAGE);
+
     let news = sqlx::query_as::<_, stock::News>(
-        "SELECT * FROM demo_app.fmp_news ORDER BY published_date desc LIMIT 50",
+        "SELECT * FROM demo_app.fmp_news
+        WHERE ($1)::timestamp IS NULL OR (published_date, id) < ($1, $2)
+        ORDER BY published_date DESC, id DESC
+        LIMIT ($3)",
     )
+    .bind(cursor.map(|t| t.time))
+    .bind(cursor.and_then(|t| t.id))
+    .bind(pagination.limit as i64)
     .fetch_all(&app.db)
     .await?;
-    let res = response::CustomResponseBuilder::new().body(news).build();
+
+    let pagination = pagination
+        .count(news.len())
+        .next_cursor(
+            news.last()
+                .map(|t| Cursor::new(t.published_date, Some(t.id))),
+        )
+        .uri(uri)
+        .build();
+
+    let res = response::CustomResponseBuilder::new()
+        .body(news)
+        .pagination(pagination)
+        .build();
     Ok(res)
 }
 
@@ -425,34 +808,82 @@ async fn other_news(
 async fn symbol_news(
     State(app): State<AppState>,
     Path(symbol): Path<String>,
+    OriginalUri(uri): OriginalUri,
+    Query(query): Query<RequestQuery>,
 ) -> Result<impl IntoResponse, Error> {
-    let s = "%".to_owned() + format!("{}", symbol).to_uppercase().as_str();
+    let cursor = Cursor::from_query(&query)?;
+    let pagination = Pagination::build_with_default(query, SYMBOL_NEWS_PAGE, NEWS_PAGE);
+
+    // tickers look like `NASDAQ:AAPL`
+    let s = symbol.to_uppercase();
 
     let news = sqlx::query_as::<_, stock::News>(
         "
     SELECT *
     FROM demo_app.fmp_news
-    WHERE SPLIT_PART(tickers,':',2) = ($1) 
-    ORDER BY published_date desc
-    LIMIT 10
+    WHERE SPLIT_PART(tickers,':',2) = ($1)
+        AND (($2)::timestamp IS NULL OR (published_date, id) < ($2, $3))
+    ORDER BY published_date DESC, id DESC
+    LIMIT ($4)
     ",
     )
     .bind(&s)
+    .bind(cursor.map(|t| t.time))
+    .bind(cursor.and_then(|t| t.id))
+    .bind(pagination.limit as i64)
     .fetch_all(&app.db)
     .await?;
 
-    let res = response::CustomResponseBuilder::new().body(news).build();
+    let pagination = pagination
+        .count(news.len())
+        .next_cursor(
+            news.last()
+                .map(|t| Cursor::new(t.published_date, Some(t.id))),
+        )
+        .uri(uri)
+        .build();
+
+    let res = response::CustomResponseBuilder::new()
+        .body(news)
+        .pagination(pagination)
+        .build();
 
     Ok(res)
 }
 
-async fn list_external_news(State(app): State<AppState>) -> Result<impl IntoResponse, Error> {
+async fn list_external_news(
+    State(app): State<AppState>,
+    OriginalUri(uri): OriginalUri,
+    Query(query): Query<RequestQuery>,
+) -> Result<impl IntoResponse, Error> {
+    let cursor = Cursor::from_query(&query)?;
+    let pagination = Pagination::build_with_limit(query, NEWS_PAGE);
+
     let news = sqlx::query_as::<_, stock::StockNews>(
-        "SELECT * FROM demo_app.stock_news ORDER BY published_date desc LIMIT 50",
+        "SELECT * FROM demo_app.stock_news
+        WHERE ($1)::timestamp IS NULL OR (published_date, id) < ($1, $2)
+        ORDER BY published_date DESC, id DESC
+        LIMIT ($3)",
     )
+    .bind(cursor.map(|t| t.time))
+    .bind(cursor.and_then(|t| t.id))
+    .bind(pagination.limit as i64)
     .fetch_all(&app.db)
     .await?;
-    let res = response::CustomResponseBuilder::new().body(news).build();
+
+    let pagination = pagination
+        .count(news.len())
+        .next_cursor(
+            news.last()
+                .map(|t| Cursor::new(t.published_date, Some(t.id))),
+        )
+        .uri(uri)
+        .build();
+
+    let res = response::CustomResponseBuilder::new()
+        .body(news)
+        .pagination(pagination)
+        .build();
     Ok(res)
 }
 
@@ -511,38 +942,14 @@ async fn symbol_price_with_company_name(
     State(app): State<AppState>,
     Path(symbol): Path<String>,
 ) -> Result<impl IntoResponse, Error> {
-    let local = Local::now();
-
-    let last_intraday_in_us = match local.weekday() {
-        Weekday::Mon => {
-            NaiveDate::from_ymd_opt(local.year(), local.month(), local.day())
-                .unwrap()
-                .and_hms_opt(9, 30, 0)
-                .unwrap()
-                - Duration::days(3)
-        }
-        Weekday::Sun => {
-            NaiveDate::from_ymd_opt(local.year(), local.month(), local.day())
-                .unwrap()
-                .and_hms_opt(9, 30, 0)
-                .unwrap()
-                - Duration::days(2)
-        }
-        _ => {
-            NaiveDate::from_ymd_opt(local.year(), local.month(), local.day())
-                .unwrap()
-                .and_hms_opt(9, 30, 0)
-                .unwrap()
-                - Duration::days(1)
-        }
-    };
+    let last_session = app.calendar.last_session(MarketCalendar::now());
 
     let s = format!("{symbol}").to_uppercase();
     let price = sqlx::query_as::<_, stock::DailyPriceWithCompany>(
         "with minute_price as (
             select time, open, close, high, low, volume
             from demo_app.stock_price
-            where symbol = ($1) and time >= '2023-09-21 09:30:00.000' and ema is null
+            where symbol = ($1) and time >= ($2)
             order by time asc), profile_cte as (
                 select symbol, price, company_name, exchange_short_name, mkt_cap, change, price_range
                 from demo_app.stock_profile
@@ -554,6 +961,7 @@ async fn symbol_price_with_company_name(
         ",
     )
     .bind(&s)
+    .bind(last_session.open_local())
     .fetch_one(&app.db)
     .await?;
 
@@ -0,0 +1,221 @@
+use crate::app_state::AppState;
+use crate::error::Error;
+use crate::model::digest::{DigestSettings, EmailConfirmation, UpdateDigestSettings};
+use crate::outbox::{self, MailKind};
+use crate::rate_limit;
+use crate::response;
+use crate::session::CurrentUser;
+use crate::template::{self, Lang};
+use axum::{
+    extract::{Json, Path, State},
+    response::{Html, IntoResponse},
+    routing::get,
+    Router,
+};
+use chrono::{Duration, Utc};
+use lettre::message::Mailbox;
+use lettre::Message;
+use serde_json::json;
+use uuid::Uuid;
+use validator::Validate;
+
+const CONFIRM_HOURS: i64 = 24;
+// confirmation mails one user can have sent, to any addresses, per window
+const CONFIRM_LIMIT: u64 = 5;
+const CONFIRM_WINDOW_SECS: usize = 60 * 60;
+
+pub fn routes(app: &mut AppState) -> Router {
+    Router::new()
+        .route(
+            "/me/digest",
+            get(digest_settings).put(update_digest_settings),
+        )
+        .route("/digest/confirm/:token", get(confirm_email))
+        .with_state(app.clone())
+}
+
+// region: --- route /me/digest
+async fn digest_settings(
+    user: CurrentUser,
+    State(app): State<AppState>,
+) -> Result<impl IntoResponse, Error> {
+    let settings = sqlx::query_as::<_, DigestSettings>(
+        "SELECT u.digest_opt_in, u.email, u.language, ec.email as pending_email
+        FROM weblog.user u
+        LEFT JOIN weblog.email_confirmation ec
+            ON ec.usr_id = u.usr_id AND ec.expires_at > current_timestamp
+        WHERE u.usr_id = ($1)",
+    )
+    .bind(user.usr_id)
+    .fetch_one(&app.db)
+    .await?;
+
+    let res = response::CustomResponseBuilder::new()
+        .body(settings)
+        .build();
+
+    Ok(res)
+}
+
+/// A new `email` is not stored until the link sent to it is followed. Until then the digest
+/// keeps going to the confirmed address, or stays off when there is none.
+async fn update_digest_settings(
+    user: CurrentUser,
+    State(app): State<AppState>,
+    Json(body): Json<UpdateDigestSettings>,
+) -> Result<impl IntoResponse, Error> {
+    body.validate()?;
+
+    let language = body.language.map(|t| Lang::from_tag(&t).tag());
+
+    let mut transaction = app.db.begin().await?;
+
+    let (username, email) = sqlx::query_as::<_, (String, Option<String>)>(
+        "SELECT username, email FROM weblog.user WHERE usr_id = ($1) FOR UPDATE",
+    )
+    .bind(user.usr_id)
+    .fetch_one(&mut transaction)
+    .await?;
+
+    let new_email = body.email.filter(|t| email.as_ref() != Some(t));
+
+    if body.digest_opt_in && email.is_none() && new_email.is_none() {
+        return Err(Error::BADREQUEST(
+            "An email address is required for the daily digest.".to_string(),
+        ));
+    }
+
+    let settings = sqlx::query_as::<_, DigestSettings>(
+        "UPDATE weblog.user
+        SET digest_opt_in = ($1),
+            language = coalesce(($2), language),
+            updated_at = current_timestamp
+        WHERE usr_id = ($3)
+        RETURNING digest_opt_in, email, language, NULL::varchar as pending_email",
+    )
+    .bind(body.digest_opt_in && email.is_some())
+    .bind(language)
+    .bind(user.usr_id)
+    .fetch_one(&mut transaction)
+    .await?;
+
+    let settings = match new_email {
+        Some(new_email) => {
+            let to = new_email.parse::<Mailbox>().map_err(|_| {
+                Error::BADREQUEST(format!("`{new_email}` is not a valid email address."))
+            })?;
+
+            rate_limit::check(
+                &app.redis,
+                &format!("digest:confirm:{}", user.usr_id),
+                CONFIRM_LIMIT,
+                CONFIRM_WINDOW_SECS,
+            )
+            .await?;
+
+            // a newer address replaces the one still waiting
+            let expires_at = Utc::now() + Duration::hours(CONFIRM_HOURS);
+            let (token,) = sqlx::query_as::<_, (Uuid,)>(
+                "INSERT INTO weblog.email_confirmation(usr_id, email, digest_opt_in, expires_at)
+                VALUES ($1, $2, $3, $4)
+                ON CONFLICT (usr_id) DO UPDATE
+                SET token = uuid_generate_v4(), email = excluded.email,
+                    digest_opt_in = excluded.digest_opt_in, outbox_id = NULL,
+                    expires_at = excluded.expires_at, created_at = current_timestamp
+                RETURNING token",
+            )
+            .bind(user.usr_id)
+            .bind(&new_email)
+            .bind(body.digest_opt_in)
+            .bind(expires_at)
+            .fetch_one(&mut transaction)
+            .await?;
+
+            let rendered = template::render(
+                "confirm_email",
+                Lang::from_tag(&settings.language),
+                json!({
+                    "username": username,
+                    "link": format!("{}/api/digest/confirm/{}", app.mailer.public_url, token.simple()),
+                    "expires_at": expires_at.format("%Y-%m-%d %H:%M UTC").to_string(),
+                }),
+            )?;
+
+            let message = Message::builder()
+                .from(app.mailer.sender.clone())
+                .to(to)
+                .subject(rendered.subject.as_str())
+                .multipart(rendered.multipart())
+                .map_err(|e| Error::CUSTOMERROR(e.to_string()))?;
+
+            let outbox_id =
+                outbox::enqueue(&mut transaction, MailKind::ConfirmEmail, &message).await?;
+
+            sqlx::query("UPDATE weblog.email_confirmation SET outbox_id = ($1) WHERE token = ($2)")
+                .bind(outbox_id)
+                .bind(token)
+                .execute(&mut transaction)
+                .await?;
+
+            DigestSettings {
+                pending_email: Some(new_email),
+                ..settings
+            }
+        }
+        None => settings,
+    };
+
+    transaction.commit().await?;
+
+    let res = response::CustomResponseBuilder::new()
+        .body(settings)
+        .build();
+
+    Ok(res)
+}
+
+/// Followed from the confirmation mail, the token stands in for the session.
+async fn confirm_email(
+    State(app): State<AppState>,
+    Path(token): Path<Uuid>,
+) -> Result<impl IntoResponse, Error> {
+    let mut transaction = app.db.begin().await?;
+
+    let confirmation = sqlx::query_as::<_, EmailConfirmation>(
+        "DELETE FROM weblog.email_confirmation WHERE token = ($1)
+        RETURNING usr_id, email, digest_opt_in, expires_at <= current_timestamp as expired",
+    )
+    .bind(token)
+    .fetch_optional(&mut transaction)
+    .await?;
+
+    let Some(confirmation) = confirmation.filter(|t| !t.expired) else {
+        transaction.commit().await?;
+        return Err(Error::NOTFOUND(format!(
+            "confirmation : `{}`",
+            token.simple()
+        )));
+    };
+
+    let (language,) = sqlx::query_as::<_, (String,)>(
+        "UPDATE weblog.user
+        SET email = ($1), digest_opt_in = ($2), updated_at = current_timestamp
+        WHERE usr_id = ($3)
+        RETURNING language",
+    )
+    .bind(&confirmation.email)
+    .bind(confirmation.digest_opt_in)
+    .bind(confirmation.usr_id)
+    .fetch_one(&mut transaction)
+    .await?;
+
+    transaction.commit().await?;
+
+    let message = match Lang::from_tag(&language) {
+        Lang::ZhTw => "已確認電子郵件地址，之後的每日摘要與提醒會寄到這裡。",
+        Lang::En => "Your email address is confirmed, the digest and alerts will be sent here.",
+    };
+
+    Ok(Html(format!("<p>{message}</p>")))
+}
+// endregion: --- route /me/digest
@@ -0,0 +1,478 @@
+use crate::app_state::AppState;
+use crate::error::Error;
+use crate::market_calendar::MarketCalendar;
+use crate::model::watchlist::{
+    AddSymbol, ReorderSymbols, SaveWatchlist, ShareLink, SharedWatchlist, Watchlist,
+    WatchlistDetail, WatchlistMember, WatchlistQuote, WatchlistSummary,
+};
+use crate::pagination::{Pagination, RequestQuery};
+use crate::response;
+use crate::session::CurrentUser;
+use axum::{
+    extract::{Json, OriginalUri, Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    routing::{delete, get, post, put},
+    Router,
+};
+use sqlx::{PgExecutor, PgPool};
+use std::collections::HashSet;
+use uuid::Uuid;
+use validator::Validate;
+
+const MAX_WATCHLISTS: i64 = 20;
+const MAX_SYMBOLS: i64 = 50;
+
+pub fn routes(app: &mut AppState) -> Router {
+    Router::new()
+        .route("/watchlists", get(list_watchlists).post(create_watchlist))
+        .route(
+            "/watchlists/:id",
+            get(watchlist)
+                .put(rename_watchlist)
+                .delete(delete_watchlist),
+        )
+        .route("/watchlists/:id/symbols", post(add_symbol))
+        .route("/watchlists/:id/symbols/:symbol", delete(remove_symbol))
+        .route("/watchlists/:id/order", put(reorder_symbols))
+        .route("/watchlists/:id/quotes", get(watchlist_quotes))
+        .route(
+            "/watchlists/:id/share",
+            post(share_watchlist).delete(unshare_watchlist),
+        )
+        .route("/shared/watchlists/:token", get(shared_watchlist))
+        .with_state(app.clone())
+}
+
+fn is_unique_violation(e: &sqlx::Error) -> bool {
+    matches!(e, sqlx::Error::Database(e) if e.code().as_deref() == Some("23505"))
+}
+
+/// The watchlist if `usr_id` owns it. Someone else's list is reported as missing.
+async fn owned_watchlist<'e, E>(executor: E, usr_id: i32, id: i32) -> Result<Watchlist, Error>
+where
+    E: PgExecutor<'e>,
+{
+    let watchlist = sqlx::query_as::<_, Watchlist>(
+        "SELECT id, name, share_token, created_at, updated_at
+        FROM weblog.watchlist WHERE id = ($1) AND usr_id = ($2)",
+    )
+    .bind(id)
+    .bind(usr_id)
+    .fetch_optional(executor)
+    .await?;
+
+    watchlist.ok_or_else(|| Error::NOTFOUND(format!("watchlist : `{id}`")))
+}
+
+async fn touch(db: &PgPool, id: i32) -> Result<(), Error> {
+    sqlx::query("UPDATE weblog.watchlist SET updated_at = current_timestamp WHERE id = ($1)")
+        .bind(id)
+        .execute(db)
+        .await?;
+
+    Ok(())
+}
+
+/// Latest price, change and a sparkline of the last session for every member, in list order.
+async fn quotes(app: &AppState, id: i32) -> Result<Vec<WatchlistQuote>, Error> {
+    let last_session = app.calendar.last_session(MarketCalendar::now());
+
+    let quotes = sqlx::query_as::<_, WatchlistQuote>(
+        "SELECT ws.symbol, sp.company_name, sp.img, sp.price, sp.change,
+            CASE WHEN sp.price - sp.change <> 0
+                THEN sp.change / (sp.price - sp.change) * 100 END as change_percent,
+            coalesce(spark.points, '{}') as sparkline
+        FROM weblog.watchlist_symbol ws
+        JOIN demo_app.stock_profile sp ON sp.symbol = ws.symbol
+        LEFT JOIN LATERAL (
+            SELECT array_agg(b.close ORDER BY b.bucket) as points
+            FROM (
+                SELECT time_bucket('15 minutes', time) as bucket, last(close, time) as close
+                FROM demo_app.stock_price
+                WHERE symbol = ws.symbol AND time >= ($2)
+                GROUP BY bucket
+            ) b
+        ) spark ON true
+        WHERE ws.watchlist_id = ($1)
+        ORDER BY ws.position ASC, ws.symbol ASC",
+    )
+    .bind(id)
+    .bind(last_session.open_local())
+    .fetch_all(&app.db)
+    .await?;
+
+    Ok(quotes)
+}
+
+// region: --- route /watchlists
+async fn list_watchlists(
+    user: CurrentUser,
+    State(app): State<AppState>,
+    OriginalUri(uri): OriginalUri,
+    Query(query): Query<RequestQuery>,
+) -> Result<impl IntoResponse, Error> {
+    let pagination = Pagination::build_from_request_query(query);
+
+    let watchlists = sqlx::query_as::<_, WatchlistSummary>(
+        "SELECT w.id, w.name, count(ws.symbol) as symbol_count,
+            w.share_token IS NOT NULL as shared, w.created_at, w.updated_at
+        FROM weblog.watchlist w
+        LEFT JOIN weblog.watchlist_symbol ws ON ws.watchlist_id = w.id
+        WHERE w.usr_id = ($1)
+        GROUP BY w.id
+        ORDER BY w.created_at ASC, w.id ASC
+        LIMIT ($2) OFFSET ($3)",
+    )
+    .bind(user.usr_id)
+    .bind(pagination.limit as i64)
+    .bind(pagination.offset as i64)
+    .fetch_all(&app.db)
+    .await?;
+
+    let pagination = pagination.count(watchlists.len()).uri(uri).build();
+
+    let res = response::CustomResponseBuilder::new()
+        .body(watchlists)
+        .pagination(pagination)
+        .build();
+
+    Ok(res)
+}
+
+async fn create_watchlist(
+    user: CurrentUser,
+    State(app): State<AppState>,
+    Json(mut body): Json<SaveWatchlist>,
+) -> Result<impl IntoResponse, Error> {
+    body.name = body.name.trim().to_string();
+    body.validate()?;
+
+    let (owned,) =
+        sqlx::query_as::<_, (i64,)>("SELECT count(*) FROM weblog.watchlist WHERE usr_id = ($1)")
+            .bind(user.usr_id)
+            .fetch_one(&app.db)
+            .await?;
+
+    if owned >= MAX_WATCHLISTS {
+        return Err(Error::BADREQUEST(format!(
+            "A user can have at most {MAX_WATCHLISTS} watchlists."
+        )));
+    }
+
+    let watchlist = sqlx::query_as::<_, Watchlist>(
+        "INSERT INTO weblog.watchlist(usr_id, name) VALUES ($1, $2)
+        RETURNING id, name, share_token, created_at, updated_at",
+    )
+    .bind(user.usr_id)
+    .bind(&body.name)
+    .fetch_one(&app.db)
+    .await
+    .map_err(|e| match is_unique_violation(&e) {
+        true => Error::BADREQUEST(format!("watchlist `{}` already exists.", body.name)),
+        false => e.into(),
+    })?;
+
+    let res = response::CustomResponseBuilder::new()
+        .body(watchlist)
+        .status_code(StatusCode::CREATED)
+        .build();
+
+    Ok(res)
+}
+// endregion: --- route /watchlists
+
+// region: --- route /watchlists/:id
+async fn watchlist(
+    user: CurrentUser,
+    State(app): State<AppState>,
+    Path(id): Path<i32>,
+) -> Result<impl IntoResponse, Error> {
+    let watchlist = owned_watchlist(&app.db, user.usr_id, id).await?;
+
+    let symbols = sqlx::query_as::<_, WatchlistMember>(
+        "SELECT ws.symbol, sp.company_name, ws.position, ws.added_at
+        FROM weblog.watchlist_symbol ws
+        JOIN demo_app.stock_profile sp ON sp.symbol = ws.symbol
+        WHERE ws.watchlist_id = ($1)
+        ORDER BY ws.position ASC, ws.symbol ASC",
+    )
+    .bind(id)
+    .fetch_all(&app.db)
+    .await?;
+
+    let res = response::CustomResponseBuilder::new()
+        .body(WatchlistDetail { watchlist, symbols })
+        .build();
+
+    Ok(res)
+}
+
+async fn rename_watchlist(
+    user: CurrentUser,
+    State(app): State<AppState>,
+    Path(id): Path<i32>,
+    Json(mut body): Json<SaveWatchlist>,
+) -> Result<impl IntoResponse, Error> {
+    body.name = body.name.trim().to_string();
+    body.validate()?;
+
+    let watchlist = sqlx::query_as::<_, Watchlist>(
+        "UPDATE weblog.watchlist SET name = ($1), updated_at = current_timestamp
+        WHERE id = ($2) AND usr_id = ($3)
+        RETURNING id, name, share_token, created_at, updated_at",
+    )
+    .bind(&body.name)
+    .bind(id)
+    .bind(user.usr_id)
+    .fetch_optional(&app.db)
+    .await
+    .map_err(|e| match is_unique_violation(&e) {
+        true => Error::BADREQUEST(format!("watchlist `{}` already exists.", body.name)),
+        false => e.into(),
+    })?;
+
+    match watchlist {
+        Some(t) => Ok(response::CustomResponseBuilder::new().body(t).build()),
+        None => Err(Error::NOTFOUND(format!("watchlist : `{id}`"))),
+    }
+}
+
+async fn delete_watchlist(
+    user: CurrentUser,
+    State(app): State<AppState>,
+    Path(id): Path<i32>,
+) -> Result<impl IntoResponse, Error> {
+    let deleted = sqlx::query("DELETE FROM weblog.watchlist WHERE id = ($1) AND usr_id = ($2)")
+        .bind(id)
+        .bind(user.usr_id)
+        .execute(&app.db)
+        .await?;
+
+    if deleted.rows_affected() == 0 {
+        return Err(Error::NOTFOUND(format!("watchlist : `{id}`")));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+// endregion: --- route /watchlists/:id
+
+// region: --- route /watchlists/:id/symbols
+async fn add_symbol(
+    user: CurrentUser,
+    State(app): State<AppState>,
+    Path(id): Path<i32>,
+    Json(body): Json<AddSymbol>,
+) -> Result<impl IntoResponse, Error> {
+    owned_watchlist(&app.db, user.usr_id, id).await?;
+
+    let symbol = body.symbol.trim().to_uppercase();
+
+    let known = sqlx::query_as::<_, (String,)>(
+        "SELECT symbol FROM demo_app.stock_profile WHERE symbol = ($1)",
+    )
+    .bind(&symbol)
+    .fetch_optional(&app.db)
+    .await?;
+
+    if known.is_none() {
+        return Err(Error::NOTFOUND(format!("symbol : `{symbol}`")));
+    }
+
+    // new members go to the end, adding one twice leaves it where it was
+    let added = sqlx::query(
+        "INSERT INTO weblog.watchlist_symbol(watchlist_id, symbol, position)
+        SELECT ($1), ($2), coalesce(max(position) + 1, 0)
+        FROM weblog.watchlist_symbol WHERE watchlist_id = ($1)
+        HAVING count(*) < ($3)
+        ON CONFLICT (watchlist_id, symbol) DO NOTHING",
+    )
+    .bind(id)
+    .bind(&symbol)
+    .bind(MAX_SYMBOLS)
+    .execute(&app.db)
+    .await?;
+
+    if added.rows_affected() == 0 {
+        let (members,) = sqlx::query_as::<_, (i64,)>(
+            "SELECT count(*) FROM weblog.watchlist_symbol WHERE watchlist_id = ($1)",
+        )
+        .bind(id)
+        .fetch_one(&app.db)
+        .await?;
+
+        if members >= MAX_SYMBOLS {
+            return Err(Error::BADREQUEST(format!(
+                "A watchlist can hold at most {MAX_SYMBOLS} symbols."
+            )));
+        }
+    }
+
+    touch(&app.db, id).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn remove_symbol(
+    user: CurrentUser,
+    State(app): State<AppState>,
+    Path((id, symbol)): Path<(i32, String)>,
+) -> Result<impl IntoResponse, Error> {
+    owned_watchlist(&app.db, user.usr_id, id).await?;
+
+    let symbol = symbol.to_uppercase();
+
+    let removed = sqlx::query(
+        "DELETE FROM weblog.watchlist_symbol WHERE watchlist_id = ($1) AND symbol = ($2)",
+    )
+    .bind(id)
+    .bind(&symbol)
+    .execute(&app.db)
+    .await?;
+
+    if removed.rows_affected() == 0 {
+        return Err(Error::NOTFOUND(format!("symbol : `{symbol}`")));
+    }
+
+    touch(&app.db, id).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+// endregion: --- route /watchlists/:id/symbols
+
+// region: --- route /watchlists/:id/order
+async fn reorder_symbols(
+    user: CurrentUser,
+    State(app): State<AppState>,
+    Path(id): Path<i32>,
+    Json(body): Json<ReorderSymbols>,
+) -> Result<impl IntoResponse, Error> {
+    let mut transaction = app.db.begin().await?;
+
+    owned_watchlist(&mut transaction, user.usr_id, id).await?;
+
+    // lock the members so a concurrent add can't slip in between the check and the update
+    let members = sqlx::query_as::<_, (String,)>(
+        "SELECT symbol FROM weblog.watchlist_symbol WHERE watchlist_id = ($1) FOR UPDATE",
+    )
+    .bind(id)
+    .fetch_all(&mut transaction)
+    .await?;
+
+    let order: Vec<String> = body.symbols.iter().map(|t| t.to_uppercase()).collect();
+    let members: HashSet<String> = members.into_iter().map(|(t,)| t).collect();
+    let requested: HashSet<String> = order.iter().cloned().collect();
+
+    if requested.len() != order.len() || requested != members {
+        return Err(Error::BADREQUEST(
+            "symbols must list every member of the watchlist exactly once.".to_string(),
+        ));
+    }
+
+    sqlx::query(
+        "UPDATE weblog.watchlist_symbol ws SET position = o.ord::int4 - 1
+        FROM unnest(($2)::varchar[]) WITH ORDINALITY AS o(symbol, ord)
+        WHERE ws.watchlist_id = ($1) AND ws.symbol = o.symbol",
+    )
+    .bind(id)
+    .bind(&order)
+    .execute(&mut transaction)
+    .await?;
+
+    sqlx::query("UPDATE weblog.watchlist SET updated_at = current_timestamp WHERE id = ($1)")
+        .bind(id)
+        .execute(&mut transaction)
+        .await?;
+
+    transaction.commit().await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+// endregion: --- route /watchlists/:id/order
+
+// region: --- route /watchlists/:id/quotes
+async fn watchlist_quotes(
+    user: CurrentUser,
+    State(app): State<AppState>,
+    Path(id): Path<i32>,
+) -> Result<impl IntoResponse, Error> {
+    owned_watchlist(&app.db, user.usr_id, id).await?;
+
+    let quotes = quotes(&app, id).await?;
+
+    let res = response::CustomResponseBuilder::new().body(quotes).build();
+
+    Ok(res)
+}
+// endregion: --- route /watchlists/:id/quotes
+
+// region: --- route /watchlists/:id/share
+async fn share_watchlist(
+    user: CurrentUser,
+    State(app): State<AppState>,
+    Path(id): Path<i32>,
+) -> Result<impl IntoResponse, Error> {
+    owned_watchlist(&app.db, user.usr_id, id).await?;
+
+    // sharing again keeps the link that is already out there
+    let (token,) = sqlx::query_as::<_, (Uuid,)>(
+        "UPDATE weblog.watchlist SET share_token = coalesce(share_token, ($1))
+        WHERE id = ($2)
+        RETURNING share_token",
+    )
+    .bind(Uuid::new_v4())
+    .bind(id)
+    .fetch_one(&app.db)
+    .await?;
+
+    let res = response::CustomResponseBuilder::new()
+        .body(ShareLink {
+            token,
+            path: format!("/api/shared/watchlists/{token}"),
+        })
+        .build();
+
+    Ok(res)
+}
+
+async fn unshare_watchlist(
+    user: CurrentUser,
+    State(app): State<AppState>,
+    Path(id): Path<i32>,
+) -> Result<impl IntoResponse, Error> {
+    owned_watchlist(&app.db, user.usr_id, id).await?;
+
+    sqlx::query("UPDATE weblog.watchlist SET share_token = NULL WHERE id = ($1)")
+        .bind(id)
+        .execute(&app.db)
+        .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+// endregion: --- route /watchlists/:id/share
+
+// region: --- route /shared/watchlists/:token
+async fn shared_watchlist(
+    State(app): State<AppState>,
+    Path(token): Path<Uuid>,
+) -> Result<impl IntoResponse, Error> {
+    let watchlist = sqlx::query_as::<_, (i32, String)>(
+        "SELECT id, name FROM weblog.watchlist WHERE share_token = ($1)",
+    )
+    .bind(token)
+    .fetch_optional(&app.db)
+    .await?;
+
+    let Some((id, name)) = watchlist else {
+        return Err(Error::NOTFOUND("shared watchlist".to_string()));
+    };
+
+    let quotes = quotes(&app, id).await?;
+
+    let res = response::CustomResponseBuilder::new()
+        .body(SharedWatchlist { name, quotes })
+        .build();
+
+    Ok(res)
+}
+// endregion: --- route /shared/watchlists/:token
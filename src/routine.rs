@@ -1,31 +1,61 @@
+use crate::alerting;
 use crate::app_state::AppState;
+use crate::digest;
+use crate::indicator;
+use crate::market_calendar::MarketCalendar;
+use crate::model::market;
 use crate::model::stock;
 use chrono::prelude::*;
-use chrono::{Duration, NaiveDate, Utc};
-use chrono_tz::Asia::Taipei;
+use chrono_tz::America::New_York;
 use cron::Schedule;
 use std::error::Error;
 use std::str::FromStr;
+// EMA and SMA periods stored for every daily bar
+pub const DAILY_PERIODS: [usize; 3] = [5, 20, 60];
+pub const RSI_PERIOD: usize = 14;
+// enough closes for the 60 day EMA to settle
+const DAILY_HISTORY: i64 = 300;
+
 pub async fn routine(app: AppState) -> Result<(), Box<dyn Error>> {
-    let daily_exp = "0 30 4 * * Tue,Wed,Thu,Fri,Sat *";
+    // half an hour after the regular close, in New York time so DST moves with it
+    let daily_exp = "0 30 16 * * Mon,Tue,Wed,Thu,Fri *";
 
     let schedule = Schedule::from_str(daily_exp)?;
 
     tokio::spawn(async move {
         loop {
-            let next = schedule.upcoming(Taipei).next().unwrap();
+            let next = schedule.upcoming(New_York).next().unwrap();
 
-            let delay = (next - Utc::now().with_timezone(&Taipei)).num_milliseconds() as u64;
+            let delay = (next - MarketCalendar::now()).num_milliseconds() as u64;
 
             println!("next: {:?}, delay: {:?}", next, delay);
 
             tokio::time::sleep(tokio::time::Duration::from_millis(delay)).await;
 
+            if !app.calendar.is_trading_day(next.date_naive()) {
+                println!("{} is a market holiday, skip", next.date_naive());
+                continue;
+            }
+
             update_profile(app.clone()).await;
 
             update_minute_record(app.clone()).await;
 
+            if let Err(e) = update_index_record(app.clone()).await {
+                println!("index update failed: {e}");
+            }
+
             update_daily_price(app.clone()).await;
+
+            match alerting::evaluate_alerts(app.clone()).await {
+                Ok(fired) => println!("{fired} alerts fired"),
+                Err(e) => println!("alert evaluation failed: {e}"),
+            }
+
+            match digest::send_daily_digest(app.clone()).await {
+                Ok(queued) => println!("daily digest queued for {queued} users"),
+                Err(e) => println!("daily digest failed: {e}"),
+            }
         }
     });
     // tokio::time::sleep(tokio::time::Duration::from_millis(delay))
@@ -121,31 +151,10 @@ async fn update_minute_record(app: AppState) -> Result<(), Box<dyn Error>> {
 
     let len = symbol_list.len();
 
-    let local = Local::now();
-
-    let last_intraday = match local.weekday() {
-        chrono::Weekday::Mon => {
-            NaiveDate::from_ymd_opt(local.year(), local.month(), local.day())
-                .unwrap()
-                .and_hms_opt(9, 30, 0)
-                .unwrap()
-                - Duration::days(3)
-        }
-        chrono::Weekday::Sun => {
-            NaiveDate::from_ymd_opt(local.year(), local.month(), local.day())
-                .unwrap()
-                .and_hms_opt(9, 30, 0)
-                .unwrap()
-                - Duration::days(2)
-        }
-        _ => {
-            NaiveDate::from_ymd_opt(local.year(), local.month(), local.day())
-                .unwrap()
-                .and_hms_opt(9, 30, 0)
-                .unwrap()
-                - Duration::days(1)
-        }
-    };
+    let last_intraday = app
+        .calendar
+        .last_session(MarketCalendar::now())
+        .open_local();
 
     for i in 0..len {
         println!("index:{i}, symbol:{:?}", &symbol_list[i].symbol);
@@ -198,211 +207,210 @@ async fn update_minute_record(app: AppState) -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
-async fn update_daily_price(app: AppState) -> Result<(), Box<dyn Error>> {
+/// Latest quote and the minute bars of the last session for every row of `demo_app.indices`.
+async fn update_index_record(app: AppState) -> Result<(), Box<dyn Error>> {
     let client = app.client;
-    let symbol_list =
-      sqlx::query_as::<_, SymbolName>("SELECT symbol FROM demo_app.stock_profile WHERE is_actively_trading = TRUE ORDER BY symbol ASC")
-          .fetch_all(&app.db)
-          .await?;
 
     let base_url = std::env::var("WEB_SERVICES_URL").expect("WEB_SERVICES_URL is not set.");
     let apikey = std::env::var("WEB_SERVICES_APIKEY").expect("WEB_SERVICES_APIKEY is not set.");
 
-    let mut transaction = app.db.begin().await?;
+    // FMP wants the caret url-encoded, which is what symbol_with_html holds
+    let indices = sqlx::query_as::<_, (String, String)>(
+        "SELECT index_symbol, symbol_with_html FROM demo_app.indices ORDER BY id ASC",
+    )
+    .fetch_all(&app.db)
+    .await?;
 
-    let len = symbol_list.len();
+    let last_intraday = app
+        .calendar
+        .last_session(MarketCalendar::now())
+        .open_local();
 
-    let local = Local::now();
+    let mut transaction = app.db.begin().await?;
 
-    let last_intraday = match local.weekday() {
-        chrono::Weekday::Mon => {
-            NaiveDate::from_ymd_opt(local.year(), local.month(), local.day())
-                .unwrap()
-                .and_hms_opt(0, 0, 0)
-                .unwrap()
-                - Duration::days(3)
-        }
-        chrono::Weekday::Sun => {
-            NaiveDate::from_ymd_opt(local.year(), local.month(), local.day())
-                .unwrap()
-                .and_hms_opt(0, 0, 0)
-                .unwrap()
-                - Duration::days(2)
-        }
-        _ => {
-            NaiveDate::from_ymd_opt(local.year(), local.month(), local.day())
-                .unwrap()
-                .and_hms_opt(0, 0, 0)
-                .unwrap()
-                - Duration::days(1)
-        }
-    };
+    for (symbol, encoded) in &indices {
+        let quote = client
+            .get(format!("{base_url}/api/v3/quote/{encoded}?apikey={apikey}"))
+            .send()
+            .await?
+            .json::<Vec<market::FMPIndexQuote>>()
+            .await?;
 
-    for i in 0..len {
-        println!("index:{i}, symbol: {:?}", symbol_list[i].symbol);
-        let ema5 = base_url.to_owned()
-            + "/api/v3/technical_indicator/daily/"
-            + symbol_list[i].symbol.as_str()
-            + "?period=5&type=ema&apikey="
-            + apikey.as_str();
+        if let Some(quote) = quote.first() {
+            sqlx::query(
+                "UPDATE demo_app.indices
+                SET price = ($1), change = ($2), change_percent = ($3), day_low = ($4),
+                    day_high = ($5), previous_close = ($6), quoted_at = ($7)
+                WHERE index_symbol = ($8)",
+            )
+            .bind(quote.price)
+            .bind(quote.change)
+            .bind(quote.changes_percentage)
+            .bind(quote.day_low)
+            .bind(quote.day_high)
+            .bind(quote.previous_close)
+            .bind(
+                quote
+                    .timestamp
+                    .and_then(|t| Utc.timestamp_opt(t, 0).single()),
+            )
+            .bind(symbol)
+            .execute(&mut transaction)
+            .await?;
+        }
 
-        let ema20 = base_url.to_owned()
-            + "/api/v3/technical_indicator/daily/"
-            + symbol_list[i].symbol.as_str()
-            + "?period=20&type=ema&apikey="
-            + apikey.as_str();
+        let bars = client
+            .get(format!(
+                "{base_url}/api/v3/historical-chart/1min/{encoded}?apikey={apikey}"
+            ))
+            .send()
+            .await?
+            .json::<Vec<stock::DailyPrice>>()
+            .await?;
 
-        let ema60 = base_url.to_owned()
-            + "/api/v3/technical_indicator/daily/"
-            + symbol_list[i].symbol.as_str()
-            + "?period=60&type=ema&apikey="
-            + apikey.as_str();
+        for bar in bars {
+            let time = NaiveDateTime::parse_from_str(bar.date.as_str(), "%Y-%m-%d %H:%M:%S")?;
+            if time < last_intraday {
+                continue;
+            }
 
-        let sma5 = base_url.to_owned()
-            + "/api/v3/technical_indicator/daily/"
-            + symbol_list[i].symbol.as_str()
-            + "?period=5&type=sma&apikey="
-            + apikey.as_str();
+            // a rerun of the routine overwrites the bars it already stored
+            sqlx::query(
+                "INSERT INTO demo_app.index_price(time, symbol, open, high, low, close, volume)
+                VALUES ($1, $2, $3, $4, $5, $6, $7)
+                ON CONFLICT (symbol, time) DO UPDATE
+                SET open = excluded.open, high = excluded.high, low = excluded.low,
+                    close = excluded.close, volume = excluded.volume",
+            )
+            .bind(time)
+            .bind(symbol)
+            .bind(bar.open)
+            .bind(bar.high)
+            .bind(bar.low)
+            .bind(bar.close)
+            .bind(bar.volume)
+            .execute(&mut transaction)
+            .await?;
+        }
+    }
 
-        let sma20 = base_url.to_owned()
-            + "/api/v3/technical_indicator/daily/"
-            + symbol_list[i].symbol.as_str()
-            + "?period=20&type=sma&apikey="
-            + apikey.as_str();
+    transaction.commit().await?;
 
-        let sma60 = base_url.to_owned()
-            + "/api/v3/technical_indicator/daily/"
-            + symbol_list[i].symbol.as_str()
-            + "?period=60&type=sma&apikey="
-            + apikey.as_str();
+    Ok(())
+}
 
-        let result_ema5: Vec<stock::EmaIndicator> = client
-            .get(ema5)
-            .send()
-            .await
-            .unwrap()
-            .json::<Vec<stock::EmaIndicator>>()
-            .await
-            .unwrap()
-            .into_iter()
-            .filter(|t| {
-                let date =
-                    NaiveDateTime::parse_from_str(t.daily.date.as_str(), "%Y-%m-%d %H:%M:%S")
-                        .unwrap();
+async fn update_daily_price(app: AppState) -> Result<(), Box<dyn Error>> {
+    let client = app.client;
+    let symbol_list =
+      sqlx::query_as::<_, SymbolName>("SELECT symbol FROM demo_app.stock_profile WHERE is_actively_trading = TRUE ORDER BY symbol ASC")
+          .fetch_all(&app.db)
+          .await?;
 
-                return date > last_intraday;
-            })
-            .collect();
+    let base_url = std::env::var("WEB_SERVICES_URL").expect("WEB_SERVICES_URL is not set.");
+    let apikey = std::env::var("WEB_SERVICES_APIKEY").expect("WEB_SERVICES_APIKEY is not set.");
 
-        let result_ema20: Vec<stock::EmaIndicator> = client
-            .get(ema20)
-            .send()
-            .await
-            .unwrap()
-            .json::<Vec<stock::EmaIndicator>>()
-            .await
-            .unwrap()
-            .into_iter()
-            .filter(|t| {
-                let date =
-                    NaiveDateTime::parse_from_str(t.daily.date.as_str(), "%Y-%m-%d %H:%M:%S")
-                        .unwrap();
+    let mut transaction = app.db.begin().await?;
 
-                return date > last_intraday;
-            })
-            .collect();
+    let len = symbol_list.len();
 
-        let result_ema60: Vec<stock::EmaIndicator> = client
-            .get(ema60)
-            .send()
-            .await
-            .unwrap()
-            .json::<Vec<stock::EmaIndicator>>()
-            .await
-            .unwrap()
-            .into_iter()
-            .filter(|t| {
-                let date =
-                    NaiveDateTime::parse_from_str(t.daily.date.as_str(), "%Y-%m-%d %H:%M:%S")
-                        .unwrap();
+    // keep the bars after the session before the last
+    let last_session = app.calendar.last_session(MarketCalendar::now());
+    let last_intraday = app.calendar.previous_trading_day(last_session.date);
 
-                return date > last_intraday;
-            })
-            .collect();
+    for i in 0..len {
+        println!("index:{i}, symbol: {:?}", symbol_list[i].symbol);
+        let url = base_url.to_owned()
+            + "/api/v3/historical-price-full/"
+            + symbol_list[i].symbol.as_str()
+            + "?timeseries=5&apikey="
+            + apikey.as_str();
 
-        let result_sma5: Vec<stock::SmaIndicator> = client
-            .get(sma5)
+        let mut bars: Vec<(NaiveDate, stock::DailyPrice)> = client
+            .get(url)
             .send()
             .await
             .unwrap()
-            .json::<Vec<stock::SmaIndicator>>()
+            .json::<stock::HistroicalStockPrice>()
             .await
             .unwrap()
+            .historical
             .into_iter()
-            .filter(|t| {
-                let date =
-                    NaiveDateTime::parse_from_str(t.daily.date.as_str(), "%Y-%m-%d %H:%M:%S")
-                        .unwrap();
-
-                return date > last_intraday;
+            .filter_map(|t| {
+                let date = NaiveDate::parse_from_str(t.daily.date.as_str(), "%Y-%m-%d").ok()?;
+                (date > last_intraday).then_some((date, t.daily))
             })
             .collect();
 
-        let result_sma20: Vec<stock::SmaIndicator> = client
-            .get(sma20)
-            .send()
-            .await
-            .unwrap()
-            .json::<Vec<stock::SmaIndicator>>()
-            .await
-            .unwrap()
-            .into_iter()
-            .filter(|t| {
-                let date =
-                    NaiveDateTime::parse_from_str(t.daily.date.as_str(), "%Y-%m-%d %H:%M:%S")
-                        .unwrap();
+        // FMP lists the newest bar first
+        bars.sort_by_key(|(date, _)| *date);
 
-                return date > last_intraday;
-            })
-            .collect();
+        let Some((first, _)) = bars.first() else {
+            continue;
+        };
 
-        let result_sma60: Vec<stock::SmaIndicator> = client
-            .get(sma60)
-            .send()
-            .await
-            .unwrap()
-            .json::<Vec<stock::SmaIndicator>>()
-            .await
-            .unwrap()
-            .into_iter()
-            .filter(|t| {
-                let date =
-                    NaiveDateTime::parse_from_str(t.daily.date.as_str(), "%Y-%m-%d %H:%M:%S")
-                        .unwrap();
+        let history = sqlx::query_as::<_, (f32,)>(
+            "SELECT close FROM (
+                SELECT date, close FROM demo_app.daily_bar
+                WHERE symbol = ($1) AND date < ($2)
+                ORDER BY date DESC LIMIT ($3)
+            ) history ORDER BY date ASC",
+        )
+        .bind(&symbol_list[i].symbol)
+        .bind(first)
+        .bind(DAILY_HISTORY)
+        .fetch_all(&mut transaction)
+        .await?;
 
-                return date > last_intraday;
-            })
+        let closes: Vec<f64> = history
+            .iter()
+            .map(|(close,)| *close as f64)
+            .chain(bars.iter().map(|(_, t)| t.close as f64))
             .collect();
 
-        let len = result_ema5.len();
+        let mut series = vec![];
+        for period in DAILY_PERIODS {
+            series.push(("ema", period, indicator::ema(&closes, period)));
+            series.push(("sma", period, indicator::sma(&closes, period)));
+        }
+        series.push(("rsi", RSI_PERIOD, indicator::rsi(&closes, RSI_PERIOD)));
 
-        for s in 0..len {
-            let date = NaiveDateTime::parse_from_str(
-                result_ema5[s].daily.date.as_str(),
-                "%Y-%m-%d %H:%M:%S",
+        for (n, (date, bar)) in bars.iter().enumerate() {
+            sqlx::query(
+                "INSERT INTO demo_app.daily_bar(symbol, date, open, high, low, close, volume)
+                VALUES ($1, $2, $3, $4, $5, $6, $7)
+                ON CONFLICT (symbol, date) DO UPDATE
+                SET open = excluded.open, high = excluded.high, low = excluded.low,
+                    close = excluded.close, volume = excluded.volume",
             )
-            .unwrap();
-            sqlx::query("INSERT INTO demo_app.stock_price(time, symbol, open, close, high, low, ema, sma, volume)
-          VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)")
-          .bind(&date)
-          .bind(&symbol_list[i].symbol)
-          .bind(&result_ema5[s].daily.open)
-          .bind(&result_ema5[s].daily.close)
-          .bind(&result_ema5[s].daily.high)
-          .bind(&result_ema5[s].daily.low)
-          .bind([result_ema5[s].ema,result_ema20[s].ema, result_ema60[s].ema])
-          .bind([result_sma5[s].sma,result_sma20[s].sma, result_sma60[s].sma])
-          .bind(&result_ema5[s].daily.volume).execute(&mut transaction).await?;
+            .bind(&symbol_list[i].symbol)
+            .bind(date)
+            .bind(bar.open)
+            .bind(bar.high)
+            .bind(bar.low)
+            .bind(bar.close)
+            .bind(bar.volume)
+            .execute(&mut transaction)
+            .await?;
+
+            // a symbol with less history than the period has no value yet
+            for (name, period, values) in &series {
+                let Some(value) = values[history.len() + n] else {
+                    continue;
+                };
+
+                sqlx::query(
+                    "INSERT INTO demo_app.daily_indicator(symbol, date, indicator, params, value)
+                    VALUES ($1, $2, $3, jsonb_build_object('period', ($4)::int4), $5)
+                    ON CONFLICT (symbol, date, indicator, params) DO UPDATE SET value = excluded.value",
+                )
+                .bind(&symbol_list[i].symbol)
+                .bind(date)
+                .bind(name)
+                .bind(*period as i32)
+                .bind(value as f32)
+                .execute(&mut transaction)
+                .await?;
+            }
         }
     }
 
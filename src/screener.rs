@@ -0,0 +1,424 @@
+use crate::error::{Error, Result};
+use crate::model::screener::{Filter, Rule, ScreenRequest, ScreenRow};
+use crate::routine::RSI_PERIOD;
+use sqlx::{PgPool, Postgres, QueryBuilder};
+
+const MAX_DEPTH: usize = 8;
+const MAX_RULES: usize = 50;
+const MAX_IN_VALUES: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Text,
+    Number,
+    Bool,
+}
+
+impl Kind {
+    fn ops(&self) -> &'static [&'static str] {
+        match self {
+            Kind::Text => &["eq", "ne", "in", "contains"],
+            Kind::Number => &["eq", "ne", "lt", "lte", "gt", "gte", "between"],
+            Kind::Bool => &["eq", "ne"],
+        }
+    }
+}
+
+// each name is also a column of the `screen` subquery, the only text a request can put
+// into the sql is one of these, every value it carries goes in as a bind parameter
+const FIELDS: [(&str, Kind); 18] = [
+    ("symbol", Kind::Text),
+    ("sector", Kind::Text),
+    ("industry", Kind::Text),
+    ("country", Kind::Text),
+    ("exchange", Kind::Text),
+    ("price", Kind::Number),
+    ("mkt_cap", Kind::Number),
+    ("beta", Kind::Number),
+    ("last_div", Kind::Number),
+    ("vol_avg", Kind::Number),
+    ("dcf", Kind::Number),
+    ("dcf_upside", Kind::Number),
+    ("is_etf", Kind::Bool),
+    ("is_fund", Kind::Bool),
+    ("is_adr", Kind::Bool),
+    ("change_percent", Kind::Number),
+    ("rsi", Kind::Number),
+    ("from_52w_high", Kind::Number),
+];
+
+// change_percent compares the last two daily bars, from_52w_high the last close with the
+// highest high of the year before it, both in percent
+const SCREEN_SQL: &str = "SELECT * FROM (
+    SELECT sp.symbol, sp.company_name, sp.img, ss.sector_name AS sector, sp.industry,
+        sp.country, sp.exchange_short_name AS exchange, sp.price::float8 AS price,
+        sp.mkt_cap::float8 AS mkt_cap, sp.beta::float8 AS beta, sp.last_div::float8 AS last_div,
+        sp.vol_avg::float8 AS vol_avg, sp.dcf::float8 AS dcf,
+        CASE WHEN sp.price > 0 THEN ((sp.dcf - sp.price) / sp.price * 100)::float8 END AS dcf_upside,
+        coalesce(sp.is_etf, false) AS is_etf, coalesce(sp.is_fund, false) AS is_fund,
+        coalesce(sp.is_adr, false) AS is_adr,
+        CASE WHEN bar.prev_close > 0
+            THEN ((bar.close - bar.prev_close) / bar.prev_close * 100)::float8 END AS change_percent,
+        rsi.value::float8 AS rsi,
+        CASE WHEN bar.high_52w > 0
+            THEN ((bar.close - bar.high_52w) / bar.high_52w * 100)::float8 END AS from_52w_high
+    FROM demo_app.stock_profile sp
+    LEFT JOIN demo_app.stock_sector ss ON ss.sector_id = sp.sector_id
+    LEFT JOIN LATERAL (
+        SELECT (array_agg(db.close ORDER BY db.date DESC))[1] AS close,
+            (array_agg(db.close ORDER BY db.date DESC))[2] AS prev_close,
+            max(db.high) AS high_52w
+        FROM demo_app.daily_bar db
+        WHERE db.symbol = sp.symbol AND db.date > (
+            SELECT max(date) - 364 FROM demo_app.daily_bar WHERE symbol = sp.symbol
+        )
+    ) bar ON true
+    LEFT JOIN LATERAL (
+        SELECT di.value FROM demo_app.daily_indicator di
+        WHERE di.symbol = sp.symbol AND di.indicator = 'rsi'
+            AND di.params = jsonb_build_object('period', ";
+
+const SCREEN_SQL_TAIL: &str = "::int4)
+        ORDER BY di.date DESC LIMIT 1
+    ) rsi ON true
+    WHERE sp.symbol IS NOT NULL AND sp.is_actively_trading IS NOT FALSE
+) screen";
+
+fn field(name: &str) -> Result<(&'static str, Kind)> {
+    FIELDS
+        .iter()
+        .find(|(column, _)| *column == name)
+        .copied()
+        .ok_or_else(|| {
+            let names: Vec<&str> = FIELDS.iter().map(|(column, _)| *column).collect();
+            Error::BADREQUEST(format!(
+                "`{name}` is not a screener field, use one of {}.",
+                names.join(", ")
+            ))
+        })
+}
+
+fn comparison(op: &str) -> &'static str {
+    match op {
+        "ne" => " <> ",
+        "lt" => " < ",
+        "lte" => " <= ",
+        "gt" => " > ",
+        "gte" => " >= ",
+        _ => " = ",
+    }
+}
+
+/// The screen as one parameterized query, sorted by `mkt_cap` descending unless told otherwise.
+pub fn build(
+    request: &ScreenRequest,
+    limit: i64,
+    offset: i64,
+) -> Result<QueryBuilder<'static, Postgres>> {
+    let mut qb = QueryBuilder::new(SCREEN_SQL);
+    qb.push_bind(RSI_PERIOD as i32).push(SCREEN_SQL_TAIL);
+
+    if let Some(filter) = &request.filter {
+        qb.push(" WHERE ");
+        push_filter(&mut qb, filter, 1, &mut 0)?;
+    }
+
+    let (column, _) = field(request.sort.as_deref().unwrap_or("mkt_cap"))?;
+    let direction = match request.order.as_deref().unwrap_or("desc") {
+        "asc" => " ASC",
+        "desc" => " DESC",
+        _ => {
+            return Err(Error::BADREQUEST(
+                "order must be one of asc, desc.".to_string(),
+            ))
+        }
+    };
+
+    qb.push(" ORDER BY ")
+        .push(column)
+        .push(direction)
+        .push(" NULLS LAST, symbol ASC LIMIT ")
+        .push_bind(limit)
+        .push(" OFFSET ")
+        .push_bind(offset);
+
+    Ok(qb)
+}
+
+pub async fn screen(
+    db: &PgPool,
+    request: &ScreenRequest,
+    limit: i64,
+    offset: i64,
+) -> Result<Vec<ScreenRow>> {
+    let mut qb = build(request, limit, offset)?;
+    let rows = qb.build_query_as::<ScreenRow>().fetch_all(db).await?;
+
+    Ok(rows)
+}
+
+fn push_filter(
+    qb: &mut QueryBuilder<'static, Postgres>,
+    filter: &Filter,
+    depth: usize,
+    rules: &mut usize,
+) -> Result<()> {
+    if depth > MAX_DEPTH {
+        return Err(Error::BADREQUEST(format!(
+            "filter can nest at most {MAX_DEPTH} levels."
+        )));
+    }
+
+    match filter {
+        Filter::And { and } => push_group(qb, and, "and", depth, rules),
+        Filter::Or { or } => push_group(qb, or, "or", depth, rules),
+        Filter::Not { not } => {
+            qb.push("NOT (");
+            push_filter(qb, not, depth + 1, rules)?;
+            qb.push(")");
+            Ok(())
+        }
+        Filter::Rule(rule) => {
+            *rules += 1;
+            if *rules > MAX_RULES {
+                return Err(Error::BADREQUEST(format!(
+                    "filter can have at most {MAX_RULES} rules."
+                )));
+            }
+            push_rule(qb, rule)
+        }
+    }
+}
+
+fn push_group(
+    qb: &mut QueryBuilder<'static, Postgres>,
+    nodes: &[Filter],
+    name: &str,
+    depth: usize,
+    rules: &mut usize,
+) -> Result<()> {
+    if nodes.is_empty() {
+        return Err(Error::BADREQUEST(format!(
+            "`{name}` needs at least one filter."
+        )));
+    }
+
+    let separator = if name == "and" { " AND " } else { " OR " };
+
+    qb.push("(");
+    for (i, node) in nodes.iter().enumerate() {
+        if i > 0 {
+            qb.push(separator);
+        }
+        push_filter(qb, node, depth + 1, rules)?;
+    }
+    qb.push(")");
+
+    Ok(())
+}
+
+fn push_rule(qb: &mut QueryBuilder<'static, Postgres>, rule: &Rule) -> Result<()> {
+    let (column, kind) = field(&rule.field)?;
+    let op = rule.op.as_str();
+    let invalid =
+        |expected: &str| Error::BADREQUEST(format!("`{} {op}` needs {expected}.", rule.field));
+
+    match (kind, op) {
+        (Kind::Number, "eq" | "ne" | "lt" | "lte" | "gt" | "gte") => {
+            let value = rule.value.as_f64().ok_or_else(|| invalid("a number"))?;
+            qb.push(column).push(comparison(op)).push_bind(value);
+        }
+        (Kind::Number, "between") => {
+            let (low, high) = rule
+                .value
+                .as_array()
+                .and_then(|t| match t.as_slice() {
+                    [low, high] => Some((low.as_f64()?, high.as_f64()?)),
+                    _ => None,
+                })
+                .filter(|(low, high)| low <= high)
+                .ok_or_else(|| invalid("[low, high] with low <= high"))?;
+            qb.push(column)
+                .push(" BETWEEN ")
+                .push_bind(low)
+                .push(" AND ")
+                .push_bind(high);
+        }
+        (Kind::Text, "eq" | "ne") => {
+            let value = rule.value.as_str().ok_or_else(|| invalid("a string"))?;
+            qb.push("lower(")
+                .push(column)
+                .push(")")
+                .push(comparison(op))
+                .push("lower(")
+                .push_bind(value.to_string())
+                .push(")");
+        }
+        (Kind::Text, "in") => {
+            let values = rule
+                .value
+                .as_array()
+                .and_then(|t| {
+                    t.iter()
+                        .map(|v| v.as_str().map(str::to_lowercase))
+                        .collect::<Option<Vec<_>>>()
+                })
+                .filter(|t| !t.is_empty() && t.len() <= MAX_IN_VALUES)
+                .ok_or_else(|| invalid(&format!("a list of 1 to {MAX_IN_VALUES} strings")))?;
+            qb.push("lower(")
+                .push(column)
+                .push(") = ANY(")
+                .push_bind(values)
+                .push(")");
+        }
+        (Kind::Text, "contains") => {
+            let value = rule.value.as_str().ok_or_else(|| invalid("a string"))?;
+            let escaped = value
+                .replace('\\', "\\\\")
+                .replace('%', "\\%")
+                .replace('_', "\\_");
+            qb.push(column)
+                .push(" ILIKE ")
+                .push_bind(format!("%{escaped}%"));
+        }
+        (Kind::Bool, "eq" | "ne") => {
+            let value = rule
+                .value
+                .as_bool()
+                .ok_or_else(|| invalid("true or false"))?;
+            qb.push(column).push(comparison(op)).push_bind(value);
+        }
+        _ => {
+            return Err(Error::BADREQUEST(format!(
+                "op `{op}` does not apply to `{}`, use one of {}.",
+                rule.field,
+                kind.ops().join(", ")
+            )))
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn request(body: serde_json::Value) -> ScreenRequest {
+        serde_json::from_value(body).unwrap()
+    }
+
+    /// What follows the fixed `screen` subquery.
+    fn clauses(request: &ScreenRequest) -> Result<String> {
+        let qb = build(request, 100, 0)?;
+        Ok(qb.sql().split_once(") screen").unwrap().1.to_string())
+    }
+
+    fn message(err: Error) -> String {
+        match err {
+            Error::BADREQUEST(t) => t,
+            other => panic!("expected a bad request, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn compiles_a_tree_to_placeholders() {
+        let body = request(json!({
+            "filter": {"and": [
+                {"field": "sector", "op": "eq", "value": "Technology"},
+                {"or": [
+                    {"field": "rsi", "op": "lt", "value": 30},
+                    {"field": "from_52w_high", "op": "between", "value": [-5, 0]}
+                ]},
+                {"not": {"field": "is_etf", "op": "eq", "value": true}},
+                {"field": "country", "op": "in", "value": ["US", "TW"]}
+            ]},
+            "sort": "change_percent",
+            "order": "asc"
+        }));
+
+        assert_eq!(
+            clauses(&body).unwrap(),
+            " WHERE (lower(sector) = lower($2) AND (rsi < $3 OR from_52w_high BETWEEN $4 AND $5) \
+             AND NOT (is_etf = $6) AND lower(country) = ANY($7)) \
+             ORDER BY change_percent ASC NULLS LAST, symbol ASC LIMIT $8 OFFSET $9"
+        );
+    }
+
+    #[test]
+    fn values_never_reach_the_sql() {
+        let body = request(json!({
+            "filter": {"field": "industry", "op": "contains", "value": "x'); DROP TABLE weblog.user; --"}
+        }));
+
+        let sql = clauses(&body).unwrap();
+        assert!(sql.starts_with(" WHERE industry ILIKE $2 ORDER BY mkt_cap DESC"));
+        assert!(!sql.contains("DROP"));
+
+        let body = request(json!({"sort": "mkt_cap; DROP TABLE weblog.user"}));
+        assert!(message(clauses(&body).unwrap_err()).contains("is not a screener field"));
+    }
+
+    #[test]
+    fn checks_ops_and_values_against_the_field() {
+        let cases = [
+            (
+                json!({"field": "pe", "op": "lt", "value": 10}),
+                "is not a screener field",
+            ),
+            (
+                json!({"field": "beta", "op": "contains", "value": "1"}),
+                "does not apply",
+            ),
+            (
+                json!({"field": "beta", "op": "lt", "value": "1"}),
+                "needs a number",
+            ),
+            (
+                json!({"field": "beta", "op": "between", "value": [2, 1]}),
+                "low <= high",
+            ),
+            (
+                json!({"field": "sector", "op": "in", "value": []}),
+                "1 to 100 strings",
+            ),
+            (
+                json!({"field": "is_adr", "op": "eq", "value": 1}),
+                "true or false",
+            ),
+        ];
+
+        for (filter, expected) in cases {
+            let err = clauses(&request(json!({ "filter": filter }))).unwrap_err();
+            assert!(message(err).contains(expected), "{expected}");
+        }
+
+        let err = clauses(&request(json!({"order": "up"}))).unwrap_err();
+        assert_eq!(message(err), "order must be one of asc, desc.");
+    }
+
+    #[test]
+    fn limits_the_shape_of_the_tree() {
+        let mut filter = json!({"field": "beta", "op": "gt", "value": 1});
+        for _ in 0..MAX_DEPTH {
+            filter = json!({ "not": filter });
+        }
+        let err = clauses(&request(json!({ "filter": filter }))).unwrap_err();
+        assert!(message(err).contains("nest at most"));
+
+        let rules: Vec<_> = (0..=MAX_RULES)
+            .map(|t| json!({"field": "price", "op": "gt", "value": t}))
+            .collect();
+        let err = clauses(&request(json!({"filter": {"or": rules}}))).unwrap_err();
+        assert!(message(err).contains("at most 50 rules"));
+
+        let err = clauses(&request(json!({"filter": {"and": []}}))).unwrap_err();
+        assert_eq!(message(err), "`and` needs at least one filter.");
+
+        let unknown = serde_json::from_value::<ScreenRequest>(json!({
+            "filter": {"field": "beta", "op": "gt", "value": 1, "extra": true}
+        }));
+        assert!(unknown.is_err());
+    }
+}
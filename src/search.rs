@@ -0,0 +1,235 @@
+use crate::app_state::AppState;
+use crate::error::{Error, Result};
+use crate::model::stock::{MatchHighlight, SymbolMatch};
+use redis::AsyncCommands;
+use sqlx::PgPool;
+use tracing::error;
+
+const MAX_QUERY_LEN: usize = 64;
+const TYPEAHEAD_SIZE: i64 = 8;
+// a prefix typed this many times within the window gets its results cached
+const HOT_PREFIX_HITS: u64 = 3;
+const HOT_PREFIX_WINDOW_SECS: usize = 60;
+const TYPEAHEAD_TTL_SECS: usize = 5 * 60;
+
+/// Trimmed query, rejected when empty or longer than anything we index.
+pub fn normalize(q: &str) -> Result<String> {
+    let q = q.split_whitespace().collect::<Vec<_>>().join(" ");
+
+    if q.is_empty() {
+        return Err(Error::BADREQUEST(
+            "search query can not be empty.".to_string(),
+        ));
+    }
+    if q.chars().count() > MAX_QUERY_LEN {
+        return Err(Error::BADREQUEST(format!(
+            "search query must be at most {MAX_QUERY_LEN} characters."
+        )));
+    }
+
+    Ok(q)
+}
+
+/// Exact tickers first, then exact ISIN/CUSIP/CIK, then prefixes, then trigram matches.
+pub async fn search(db: &PgPool, q: &str, limit: i64, offset: i64) -> Result<Vec<SymbolMatch>> {
+    let escaped = q
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    // leading zeros are optional in a CIK, a query of only zeros names no CIK at all
+    let cik = Some(q.trim_start_matches('0')).filter(|t| !t.is_empty());
+
+    let mut matches = sqlx::query_as::<_, SymbolMatch>(
+        "select symbol, company_name, img, isin, cusip, cik,
+            case
+                when upper(symbol) = upper($1) then 0
+                when upper(isin) = upper($1) or upper(cusip) = upper($1)
+                    or ltrim(cik, '0') = ($6) then 1
+                when symbol ilike $2 or company_name ilike $2
+                    or isin ilike $2 or cusip ilike $2 or cik ilike $2 then 2
+                else 3
+            end as tier,
+            greatest(similarity(symbol, $1), word_similarity($1, company_name)) as score
+        from demo_app.stock_profile
+        where symbol ilike $2 or company_name ilike $3
+            or isin ilike $2 or cusip ilike $2 or cik ilike $2
+            or ltrim(cik, '0') = ($6)
+            or symbol % $1 or $1 <% company_name
+        order by tier asc, score desc, symbol asc
+        limit ($4) offset ($5)",
+    )
+    .bind(q)
+    .bind(format!("{escaped}%"))
+    .bind(format!("%{escaped}%"))
+    .bind(limit)
+    .bind(offset)
+    .bind(cik)
+    .fetch_all(db)
+    .await?;
+
+    for t in matches.iter_mut() {
+        t.highlights = highlights(t, q);
+    }
+
+    Ok(matches)
+}
+
+/// Top matches for a search box. Results for prefixes that are being typed a lot are
+/// cached in Redis, a Redis failure only costs the cache.
+pub async fn typeahead(app: &AppState, q: &str) -> Result<Vec<SymbolMatch>> {
+    let key = format!("typeahead:{}", q.to_lowercase());
+
+    let mut con = match app.redis.get_async_connection().await {
+        Ok(con) => Some(con),
+        Err(e) => {
+            error!("typeahead: redis unavailable: {e}");
+            None
+        }
+    };
+
+    if let Some(con) = con.as_mut() {
+        if let Ok(Some(cached)) = con.get::<_, Option<String>>(&key).await {
+            if let Ok(matches) = serde_json::from_str(&cached) {
+                return Ok(matches);
+            }
+        }
+    }
+
+    let matches = search(&app.db, q, TYPEAHEAD_SIZE, 0).await?;
+
+    if let Some(con) = con.as_mut() {
+        let hits_key = format!("typeahead:hits:{}", q.to_lowercase());
+        let hits: u64 = con.incr(&hits_key, 1).await.unwrap_or(0);
+        if hits == 1 {
+            let _ = con.expire::<_, ()>(&hits_key, HOT_PREFIX_WINDOW_SECS).await;
+        }
+
+        if hits >= HOT_PREFIX_HITS {
+            if let Ok(json) = serde_json::to_string(&matches) {
+                let _ = con.set_ex::<_, _, ()>(&key, json, TYPEAHEAD_TTL_SECS).await;
+            }
+        }
+    }
+
+    Ok(matches)
+}
+
+/// Where the query shows up in each field. Falls back to the individual words of the
+/// query for company names, which is where fuzzy matches land.
+fn highlights(m: &SymbolMatch, q: &str) -> Vec<MatchHighlight> {
+    let fields = [
+        ("symbol", Some(&m.symbol)),
+        ("company_name", Some(&m.company_name)),
+        ("isin", m.isin.as_ref()),
+        ("cusip", m.cusip.as_ref()),
+        ("cik", m.cik.as_ref()),
+    ];
+
+    let mut out = vec![];
+    for (field, text) in fields {
+        let Some(text) = text else {
+            continue;
+        };
+
+        if let Some((start, end)) = find(text, q) {
+            out.push(MatchHighlight {
+                field: field.to_string(),
+                start,
+                end,
+            });
+        } else if field == "company_name" {
+            for word in q.split(' ').filter(|t| t.chars().count() >= 2) {
+                if let Some((start, end)) = find(text, word) {
+                    out.push(MatchHighlight {
+                        field: field.to_string(),
+                        start,
+                        end,
+                    });
+                }
+            }
+        }
+    }
+
+    out
+}
+
+/// Case-insensitive search, as `char` offsets rather than bytes.
+fn find(text: &str, needle: &str) -> Option<(usize, usize)> {
+    let text: Vec<char> = text.chars().collect();
+    let needle: Vec<char> = needle.chars().collect();
+    if needle.is_empty() || needle.len() > text.len() {
+        return None;
+    }
+
+    (0..=text.len() - needle.len())
+        .find(|&i| {
+            text[i..]
+                .iter()
+                .zip(&needle)
+                .all(|(a, b)| a.to_lowercase().eq(b.to_lowercase()))
+        })
+        .map(|i| (i, i + needle.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apple() -> SymbolMatch {
+        SymbolMatch {
+            symbol: "AAPL".to_string(),
+            company_name: "Apple Inc.".to_string(),
+            img: None,
+            isin: Some("US0378331005".to_string()),
+            cusip: Some("037833100".to_string()),
+            cik: Some("0000320193".to_string()),
+            tier: 2,
+            score: 0.5,
+            highlights: vec![],
+        }
+    }
+
+    fn spans(highlights: Vec<MatchHighlight>) -> Vec<(String, usize, usize)> {
+        highlights
+            .into_iter()
+            .map(|t| (t.field, t.start, t.end))
+            .collect()
+    }
+
+    #[test]
+    fn normalizes_queries() {
+        assert_eq!(normalize("  apple   inc ").unwrap(), "apple inc");
+        assert!(normalize("   ").is_err());
+        assert!(normalize(&"a".repeat(MAX_QUERY_LEN + 1)).is_err());
+    }
+
+    #[test]
+    fn highlights_are_case_insensitive() {
+        assert_eq!(
+            spans(highlights(&apple(), "apple")),
+            vec![("company_name".to_string(), 0, 5)]
+        );
+        assert_eq!(
+            spans(highlights(&apple(), "aa")),
+            vec![("symbol".to_string(), 0, 2)]
+        );
+        assert_eq!(
+            spans(highlights(&apple(), "320193")),
+            vec![("cik".to_string(), 4, 10)]
+        );
+    }
+
+    #[test]
+    fn fuzzy_company_matches_highlight_words() {
+        assert_eq!(
+            spans(highlights(&apple(), "aple inc")),
+            vec![("company_name".to_string(), 6, 9)]
+        );
+    }
+
+    #[test]
+    fn offsets_count_chars() {
+        assert_eq!(find("Nestlé SA", "sa"), Some((7, 9)));
+        assert_eq!(find("abc", "abcd"), None);
+    }
+}
@@ -0,0 +1,69 @@
+use crate::app_state::AppState;
+use crate::error::Error;
+use crate::routes::WEBLOG_ID;
+use axum::{async_trait, extract::FromRequestParts, http::request::Parts};
+use tower_cookies::Cookies;
+use uuid::Uuid;
+
+/// The user behind the `weblog_id` session cookie.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct CurrentUser {
+    pub usr_id: i32,
+    pub is_admin: bool,
+}
+
+/// Only extracts for a [`CurrentUser`] whose `is_admin` flag is set.
+#[derive(Debug, Clone)]
+pub struct AdminUser;
+
+#[async_trait]
+impl FromRequestParts<AppState> for CurrentUser {
+    type Rejection = Error;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        app: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let cookies = Cookies::from_request_parts(parts, app)
+            .await
+            .map_err(|(_, message)| Error::UNAUTHORIZED(message.to_string()))?;
+
+        let Some(ss_id) = cookies
+            .get(WEBLOG_ID)
+            .and_then(|ck| Uuid::parse_str(ck.value()).ok())
+        else {
+            return Err(Error::UNAUTHORIZED("Login required.".to_string()));
+        };
+
+        let user = sqlx::query_as::<_, CurrentUser>(
+            "SELECT u.usr_id, u.is_admin
+            FROM weblog.who_is_login wl
+            JOIN weblog.user u ON u.usr_id = wl.usr_id
+            JOIN weblog.session_table st ON st.ss_id = wl.ss_id
+            WHERE wl.ss_id = ($1) AND (st.expires IS NULL OR st.expires > current_timestamp)",
+        )
+        .bind(ss_id)
+        .fetch_optional(&app.db)
+        .await?;
+
+        user.ok_or_else(|| Error::UNAUTHORIZED("Session has expired.".to_string()))
+    }
+}
+
+#[async_trait]
+impl FromRequestParts<AppState> for AdminUser {
+    type Rejection = Error;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        app: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let user = CurrentUser::from_request_parts(parts, app).await?;
+
+        if !user.is_admin {
+            return Err(Error::FORBIDDEN("Admin only.".to_string()));
+        }
+
+        Ok(AdminUser)
+    }
+}
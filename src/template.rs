@@ -0,0 +1,345 @@
+use crate::alerting::Condition;
+use crate::error::{Error, Result};
+use lettre::message::MultiPart;
+use minijinja::{AutoEscape, Environment};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::sync::OnceLock;
+
+// templates are compiled into the binary, the release image only ships the executable
+const TEMPLATES: &[(&str, &str)] = &[
+    (
+        "layout.html",
+        include_str!("../templates/email/layout.html"),
+    ),
+    ("layout.txt", include_str!("../templates/email/layout.txt")),
+    (
+        "en/contact.html",
+        include_str!("../templates/email/en/contact.html"),
+    ),
+    (
+        "en/contact.txt",
+        include_str!("../templates/email/en/contact.txt"),
+    ),
+    (
+        "zh-TW/contact.html",
+        include_str!("../templates/email/zh-TW/contact.html"),
+    ),
+    (
+        "zh-TW/contact.txt",
+        include_str!("../templates/email/zh-TW/contact.txt"),
+    ),
+    (
+        "en/digest.html",
+        include_str!("../templates/email/en/digest.html"),
+    ),
+    (
+        "en/digest.txt",
+        include_str!("../templates/email/en/digest.txt"),
+    ),
+    (
+        "zh-TW/digest.html",
+        include_str!("../templates/email/zh-TW/digest.html"),
+    ),
+    (
+        "zh-TW/digest.txt",
+        include_str!("../templates/email/zh-TW/digest.txt"),
+    ),
+    (
+        "en/alert.html",
+        include_str!("../templates/email/en/alert.html"),
+    ),
+    (
+        "en/alert.txt",
+        include_str!("../templates/email/en/alert.txt"),
+    ),
+    (
+        "zh-TW/alert.html",
+        include_str!("../templates/email/zh-TW/alert.html"),
+    ),
+    (
+        "zh-TW/alert.txt",
+        include_str!("../templates/email/zh-TW/alert.txt"),
+    ),
+    (
+        "en/confirm_email.html",
+        include_str!("../templates/email/en/confirm_email.html"),
+    ),
+    (
+        "en/confirm_email.txt",
+        include_str!("../templates/email/en/confirm_email.txt"),
+    ),
+    (
+        "zh-TW/confirm_email.html",
+        include_str!("../templates/email/zh-TW/confirm_email.html"),
+    ),
+    (
+        "zh-TW/confirm_email.txt",
+        include_str!("../templates/email/zh-TW/confirm_email.txt"),
+    ),
+];
+
+pub const NAMES: &[&str] = &["contact", "digest", "alert", "confirm_email"];
+
+static ENV: OnceLock<Environment<'static>> = OnceLock::new();
+// the subject block goes into a header, not into html, so it is rendered without escaping
+static SUBJECT_ENV: OnceLock<Environment<'static>> = OnceLock::new();
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Lang {
+    #[default]
+    En,
+    ZhTw,
+}
+
+impl Lang {
+    pub fn tag(&self) -> &'static str {
+        match self {
+            Lang::En => "en",
+            Lang::ZhTw => "zh-TW",
+        }
+    }
+
+    /// Loose BCP 47 match, anything we have no translation for falls back to English.
+    pub fn from_tag(tag: &str) -> Self {
+        let tag = tag.trim().to_lowercase().replace('_', "-");
+        if tag == "zh" || tag.starts_with("zh-tw") || tag.starts_with("zh-hant") {
+            Lang::ZhTw
+        } else {
+            Lang::En
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct RenderedMail {
+    pub subject: String,
+    pub html: String,
+    pub text: String,
+}
+
+impl RenderedMail {
+    pub fn multipart(self) -> MultiPart {
+        MultiPart::alternative_plain_html(self.text, self.html)
+    }
+}
+
+#[derive(Serialize)]
+struct Context<'a, S: Serialize> {
+    lang: &'a str,
+    #[serde(flatten)]
+    data: S,
+}
+
+fn load(env: &mut Environment<'static>) {
+    for (name, source) in TEMPLATES {
+        env.add_template(name, source)
+            .expect("email templates must compile");
+    }
+}
+
+fn env() -> &'static Environment<'static> {
+    ENV.get_or_init(|| {
+        let mut env = Environment::new();
+        load(&mut env);
+        env
+    })
+}
+
+fn subject_env() -> &'static Environment<'static> {
+    SUBJECT_ENV.get_or_init(|| {
+        let mut env = Environment::new();
+        env.set_auto_escape_callback(|_| AutoEscape::None);
+        load(&mut env);
+        env
+    })
+}
+
+/// Render the html and plain text parts of `name` from the same data.
+pub fn render<S: Serialize>(name: &str, lang: Lang, data: S) -> Result<RenderedMail> {
+    let env = env();
+    let ctx = Context {
+        lang: lang.tag(),
+        data,
+    };
+
+    let html_name = format!("{}/{name}.html", lang.tag());
+    let html = env.get_template(&html_name)?;
+    let text = env.get_template(&format!("{}/{name}.txt", lang.tag()))?;
+
+    let subject = subject_env().get_template(&html_name)?;
+
+    let mut state = subject.eval_to_state(&ctx)?;
+    let subject = state.render_block("subject")?.trim().to_string();
+
+    Ok(RenderedMail {
+        subject,
+        html: html.render(&ctx)?,
+        text: text.render(&ctx)?,
+    })
+}
+
+/// Fixture data for the admin preview.
+fn sample(name: &str, lang: Lang) -> Option<Value> {
+    match name {
+        "contact" => Some(json!({
+            "sender": "reader@example.com",
+            "subject": "Q&A: is the TSLA chart EMA 5/20/60 or <10?",
+            "body": "Hi,\nis the EMA on the daily chart 5/20/60?\n<script>alert(1)</script>",
+            "ip_address": "203.0.113.7",
+            "submitted_at": "2023-10-20 09:30:00 UTC",
+        })),
+        "digest" => Some(json!({
+            "username": "chad",
+            "date": "2023-10-20",
+            "symbols": [
+                {
+                    "symbol": "AAPL",
+                    "company_name": "Apple Inc.",
+                    "close": 174.2,
+                    "change": -1.15,
+                    "change_percent": -0.66,
+                    "ema_crosses": [{ "fast": 5, "slow": 20, "direction": "below" }],
+                    "headlines": [{
+                        "symbol": "AAPL",
+                        "id": "4f1c2e0a-6a35-4cf4-9a55-2f1b0c7d9e10",
+                        "title": "Apple suppliers brace for a slower holiday quarter",
+                        "link": "https://example.com/news/apple-suppliers",
+                        "published_date": "2023-10-20T08:12:00",
+                    }],
+                },
+                {
+                    "symbol": "NVDA",
+                    "company_name": "NVIDIA Corporation",
+                    "close": 413.87,
+                    "change": 3.2,
+                    "change_percent": 0.78,
+                    "ema_crosses": [],
+                    "headlines": [],
+                },
+            ],
+            "gainers": [{ "symbol": "TSLA", "company_name": "Tesla, Inc.", "price": 211.99, "change": 9.17 }],
+            "losers": [{ "symbol": "NFLX", "company_name": "Netflix, Inc.", "price": 346.19, "change": -9.53 }],
+        })),
+        "alert" => Some(json!({
+            "username": "chad",
+            "event": {
+                "alert_id": 7,
+                "symbol": "AAPL",
+                "kind": "price",
+                "direction": "above",
+                "threshold": 175.0,
+                "message": Condition::PriceAbove(175.0).describe("AAPL", lang),
+                "date": "2023-10-20",
+                "close": 176.38,
+                "change_percent": 1.25,
+                "volume": 58499100.0,
+                "vol_avg": 55112300.0,
+                "ema_fast": 174.91,
+                "ema_slow": 176.02,
+                "triggered_at": "2023-10-20T20:31:04Z",
+            },
+        })),
+        "confirm_email" => Some(json!({
+            "username": "chad",
+            "link": "https://example.com/api/digest/confirm/0b7e4c52b0f94d1e9a4f3c2d1e0f9a8b",
+            "expires_at": "2023-10-21 09:30 UTC",
+        })),
+        _ => None,
+    }
+}
+
+pub fn render_sample(name: &str, lang: Lang) -> Result<RenderedMail> {
+    let Some(data) = sample(name, lang) else {
+        return Err(Error::NOTFOUND(format!("template : `{name}`")));
+    };
+
+    render(name, lang, data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_template_has_a_sample_in_every_language() {
+        for name in NAMES {
+            for lang in [Lang::En, Lang::ZhTw] {
+                let rendered = render_sample(name, lang).unwrap();
+                assert!(!rendered.subject.is_empty());
+            }
+        }
+    }
+
+    #[test]
+    fn lang_from_tag() {
+        assert_eq!(Lang::from_tag("zh-TW"), Lang::ZhTw);
+        assert_eq!(Lang::from_tag("zh_Hant_TW"), Lang::ZhTw);
+        assert_eq!(Lang::from_tag("en-US"), Lang::En);
+        assert_eq!(Lang::from_tag("ja"), Lang::En);
+    }
+
+    #[test]
+    fn contact_en() {
+        let rendered = render_sample("contact", Lang::En).unwrap();
+        insta::assert_snapshot!("contact_en_subject", rendered.subject);
+        insta::assert_snapshot!("contact_en_html", rendered.html);
+        insta::assert_snapshot!("contact_en_text", rendered.text);
+    }
+
+    #[test]
+    fn digest_en() {
+        let rendered = render_sample("digest", Lang::En).unwrap();
+        insta::assert_snapshot!("digest_en_subject", rendered.subject);
+        insta::assert_snapshot!("digest_en_html", rendered.html);
+        insta::assert_snapshot!("digest_en_text", rendered.text);
+    }
+
+    #[test]
+    fn digest_zh_tw() {
+        let rendered = render_sample("digest", Lang::ZhTw).unwrap();
+        insta::assert_snapshot!("digest_zh_tw_subject", rendered.subject);
+        insta::assert_snapshot!("digest_zh_tw_html", rendered.html);
+        insta::assert_snapshot!("digest_zh_tw_text", rendered.text);
+    }
+
+    #[test]
+    fn alert_en() {
+        let rendered = render_sample("alert", Lang::En).unwrap();
+        insta::assert_snapshot!("alert_en_subject", rendered.subject);
+        insta::assert_snapshot!("alert_en_html", rendered.html);
+        insta::assert_snapshot!("alert_en_text", rendered.text);
+    }
+
+    #[test]
+    fn alert_zh_tw() {
+        let rendered = render_sample("alert", Lang::ZhTw).unwrap();
+        insta::assert_snapshot!("alert_zh_tw_subject", rendered.subject);
+        insta::assert_snapshot!("alert_zh_tw_html", rendered.html);
+        insta::assert_snapshot!("alert_zh_tw_text", rendered.text);
+    }
+
+    #[test]
+    fn confirm_email_en() {
+        let rendered = render_sample("confirm_email", Lang::En).unwrap();
+        insta::assert_snapshot!("confirm_email_en_subject", rendered.subject);
+        insta::assert_snapshot!("confirm_email_en_html", rendered.html);
+        insta::assert_snapshot!("confirm_email_en_text", rendered.text);
+    }
+
+    #[test]
+    fn confirm_email_zh_tw() {
+        let rendered = render_sample("confirm_email", Lang::ZhTw).unwrap();
+        insta::assert_snapshot!("confirm_email_zh_tw_subject", rendered.subject);
+        insta::assert_snapshot!("confirm_email_zh_tw_html", rendered.html);
+        insta::assert_snapshot!("confirm_email_zh_tw_text", rendered.text);
+    }
+
+    #[test]
+    fn contact_zh_tw() {
+        let rendered = render_sample("contact", Lang::ZhTw).unwrap();
+        insta::assert_snapshot!("contact_zh_tw_subject", rendered.subject);
+        insta::assert_snapshot!("contact_zh_tw_html", rendered.html);
+        insta::assert_snapshot!("contact_zh_tw_text", rendered.text);
+    }
+}
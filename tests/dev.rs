@@ -15,15 +15,136 @@ async fn dev() -> Result<()> {
     let req_symbols = client.do_get("/api/stock/symbol");
     req_symbols.await?.print().await?;
     // search_symbol
-    let req_search_symbol = client.do_post("/api/stock/symbol", json!({"search_string":"Tsla"}));
+    let req_search_symbol = client.do_get("/api/stock/symbol/apple?limit=5");
     req_search_symbol.await?.print().await?;
+    // typeahead
+    let req_typeahead = client.do_get("/api/stock/typeahead?q=nvd");
+    req_typeahead.await?.print().await?;
 
     let req_symbol_price = client.do_get("/api/stock/price/daily/a");
     req_symbol_price.await?.print().await?;
     // profile
     let req_profile = client.do_get("/api/stock/profile/AAPL");
     req_profile.await?.print().await?;
+    // list_internal_news, follow the `Link: rel="next"` header for the next page
+    let req_news = client.do_get("/api/stock/news?limit=5");
+    req_news.await?.print().await?;
+    // symbol_candles
+    let req_candles =
+        client.do_get("/api/stock/AAPL/candles?interval=1w&from=2023-06-01&to=2023-10-20");
+    req_candles.await?.print().await?;
+    // symbol_indicators
+    let req_indicators = client.do_get("/api/stock/AAPL/indicators?type=rsi&period=14&limit=30");
+    req_indicators.await?.print().await?;
+    // compare
+    let req_compare =
+        client.do_get("/api/stock/compare?symbols=AAPL,MSFT,NVDA&from=2023-06-01&to=2023-10-20");
+    req_compare.await?.print().await?;
+    // screen
+    let req_screen = client.do_post(
+        "/api/stock/screener?limit=20",
+        json!({
+            "filter": {"and": [
+                {"field": "sector", "op": "eq", "value": "Technology"},
+                {"field": "rsi", "op": "lt", "value": 40},
+                {"not": {"field": "is_etf", "op": "eq", "value": true}}
+            ]},
+            "sort": "from_52w_high",
+            "order": "asc"
+        }),
+    );
+    req_screen.await?.print().await?;
 
     // endregion: --- stock routes
+
+    // region: --- mail routes
+    // sendmail, run the server with `MAIL_TRANSPORT=stub` to keep this offline
+    let req_form_token = client.do_get("/api/mail/token");
+    let form_token = req_form_token.await?.json_body()?["form_token"].clone();
+    // the form is rejected as a bot when it comes back within 3 seconds
+    std::thread::sleep(std::time::Duration::from_secs(3));
+
+    let req_sendmail = client.do_post(
+        "/api/mail",
+        json!({
+            "from":"reader@example.com",
+            "subject":"hello",
+            "body":"hello from the dev test",
+            "form_token": form_token
+        }),
+    );
+    req_sendmail.await?.print().await?;
+    // list_messages, needs an admin session cookie
+    let req_messages = client.do_get("/api/admin/mail");
+    req_messages.await?.print().await?;
+    // queue_stats
+    let req_queue = client.do_get("/api/admin/mail/queue");
+    req_queue.await?.print().await?;
+    // preview_template
+    let req_preview = client.do_get("/api/admin/mail/templates/contact?lang=zh-TW&format=text");
+    req_preview.await?.print().await?;
+    // endregion: --- mail routes
+
+    // region: --- user routes
+    // digest_settings, needs a session cookie
+    let req_digest = client.do_get("/api/me/digest");
+    req_digest.await?.print().await?;
+    // confirm_email, an unknown token is not found
+    let req_confirm = client.do_get("/api/digest/confirm/00000000000000000000000000000000");
+    req_confirm.await?.print().await?;
+    // endregion: --- user routes
+
+    // region: --- market routes
+    // market_status
+    let req_status = client.do_get("/api/market/status?tz=Asia/Taipei");
+    req_status.await?.print().await?;
+    // market_overview
+    let req_overview = client.do_get("/api/market/overview");
+    req_overview.await?.print().await?;
+    // list_indices
+    let req_indices = client.do_get("/api/indices");
+    req_indices.await?.print().await?;
+    // index_candles
+    let req_index_candles = client.do_get("/api/indices/%5EGSPC/candles?interval=15m&limit=30");
+    req_index_candles.await?.print().await?;
+    // endregion: --- market routes
+
+    // region: --- watchlist routes
+    // list_watchlists, needs a session cookie
+    let req_watchlists = client.do_get("/api/watchlists");
+    req_watchlists.await?.print().await?;
+
+    // watchlist_quotes
+    let req_quotes = client.do_get("/api/watchlists/1/quotes");
+    req_quotes.await?.print().await?;
+    // endregion: --- watchlist routes
+
+    // region: --- portfolio routes
+    // portfolio_summary, needs a session cookie
+    let req_summary = client.do_get("/api/portfolios/1");
+    req_summary.await?.print().await?;
+
+    // portfolio_history
+    let req_history = client.do_get("/api/portfolios/1/history?from=2023-01-01");
+    req_history.await?.print().await?;
+
+    // portfolio_allocation
+    let req_allocation = client.do_get("/api/portfolios/1/allocation");
+    req_allocation.await?.print().await?;
+    // endregion: --- portfolio routes
+
+    // region: --- alert routes
+    // list_alerts, needs a session cookie
+    let req_alerts = client.do_get("/api/alerts");
+    req_alerts.await?.print().await?;
+
+    // rotate_secret, the only response besides create that shows the webhook secret
+    let req_secret = client.do_post("/api/alerts/1/secret", json!({}));
+    req_secret.await?.print().await?;
+
+    // list_triggers
+    let req_triggers = client.do_get("/api/alerts/1/triggers");
+    req_triggers.await?.print().await?;
+    // endregion: --- alert routes
     Ok(())
 }